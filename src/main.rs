@@ -6,8 +6,18 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 mod plugins;
 use plugins::player::PlayerPlugin;
 
+use crate::plugins::actions::ActionsPlugin;
+use crate::plugins::camera::CameraPlugin;
+use crate::plugins::camera_effects::CameraEffectsPlugin;
 use crate::plugins::character_controller::CharacterControllerPlugin;
+use crate::plugins::cursor::CursorPlugin;
+use crate::plugins::dungeon::DungeonPlugin;
+use crate::plugins::enemy::EnemyPlugin;
+use crate::plugins::hud::HudPlugin;
+use crate::plugins::navigation::NavigationPlugin;
+use crate::plugins::spectator::SpectatorPlugin;
 use crate::plugins::testbed::Testbed;
+use crate::plugins::view_model::ViewModelPlugin;
 
 fn main() {
     App::new()
@@ -20,10 +30,19 @@ fn main() {
             ..default()
         }))
         .add_plugins((
-            EguiPlugin::default(),
-            WorldInspectorPlugin::new(),
+            (EguiPlugin::default(), WorldInspectorPlugin::new()),
+            ActionsPlugin,
             PlayerPlugin,
+            CameraPlugin,
+            CameraEffectsPlugin,
+            CursorPlugin,
+            HudPlugin,
+            SpectatorPlugin,
+            ViewModelPlugin,
             Testbed,
+            DungeonPlugin,
+            NavigationPlugin,
+            EnemyPlugin,
             CharacterControllerPlugin,
         ))
         .run();
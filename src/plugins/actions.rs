@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    input::{InputSystems, mouse::AccumulatedMouseMotion},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::plugins::settings;
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActionState>()
+            .add_systems(PreStartup, load_action_map)
+            .add_systems(
+                PreUpdate,
+                update_action_state
+                    .after(InputSystems)
+                    .in_set(ActionSystems),
+            )
+            .add_systems(Last, save_action_map);
+    }
+}
+
+/// Turns raw input into the [`ActionState`]. Anything that filters raw input should run before it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSystems;
+
+/// The settings file the player's bindings are stored in.
+const ACTION_MAP_FILE: &str = "input.ron";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ButtonAction {
+    Jump,
+    Crouch,
+    Sprint,
+    Fire,
+    Interact,
+    ToggleView,
+    ToggleSpectator,
+    RegenerateDungeon,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AxisAction {
+    Move,
+    /// Relative look motion, such as mouse movement, in pixels.
+    Look,
+    /// Look input that turns the camera at a rate, such as a stick, in the range `[-1, 1]`.
+    Turn,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ButtonBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+/// A physical input that produces a 2D value, with X pointing right and Y pointing up or forward.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AxisBinding {
+    Keys {
+        up: KeyCode,
+        down: KeyCode,
+        left: KeyCode,
+        right: KeyCode,
+    },
+    MouseMotion,
+    GamepadStick {
+        x: GamepadAxis,
+        y: GamepadAxis,
+        #[serde(default)]
+        response: StickResponse,
+    },
+}
+
+/// How raw stick deflection is shaped before it reaches the game.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StickResponse {
+    /// Deflection below this is ignored, to hide stick drift.
+    pub inner_deadzone: f32,
+    /// Deflection above this counts as fully pushed, since worn sticks rarely reach 1.
+    pub outer_deadzone: f32,
+    /// Values above 1 give finer control near the center of the stick.
+    pub exponent: f32,
+}
+
+impl Default for StickResponse {
+    fn default() -> Self {
+        Self {
+            inner_deadzone: 0.15,
+            outer_deadzone: 0.95,
+            exponent: 1.0,
+        }
+    }
+}
+
+impl StickResponse {
+    /// Applies a radial deadzone and the response curve, keeping the direction of `value`.
+    pub fn apply(&self, value: Vec2) -> Vec2 {
+        let length = value.length();
+        if length <= self.inner_deadzone {
+            return Vec2::ZERO;
+        }
+
+        let range = (self.outer_deadzone - self.inner_deadzone).max(f32::EPSILON);
+        let magnitude = ((length - self.inner_deadzone) / range).clamp(0.0, 1.0);
+        value / length * magnitude.powf(self.exponent)
+    }
+}
+
+/// Maps physical inputs to the logical actions the game reacts to.
+///
+/// Changes made at runtime are written back to the config file.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct ActionMap {
+    pub buttons: BTreeMap<ButtonAction, Vec<ButtonBinding>>,
+    pub axes: BTreeMap<AxisAction, Vec<AxisBinding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        let buttons = BTreeMap::from([
+            (
+                ButtonAction::Jump,
+                vec![
+                    ButtonBinding::Key(KeyCode::Space),
+                    ButtonBinding::Gamepad(GamepadButton::South),
+                ],
+            ),
+            (
+                ButtonAction::Crouch,
+                vec![
+                    ButtonBinding::Key(KeyCode::ControlLeft),
+                    ButtonBinding::Key(KeyCode::KeyC),
+                    ButtonBinding::Gamepad(GamepadButton::East),
+                ],
+            ),
+            (
+                ButtonAction::Sprint,
+                vec![
+                    ButtonBinding::Key(KeyCode::ShiftLeft),
+                    ButtonBinding::Gamepad(GamepadButton::LeftThumb),
+                ],
+            ),
+            (
+                ButtonAction::Fire,
+                vec![
+                    ButtonBinding::Mouse(MouseButton::Left),
+                    ButtonBinding::Gamepad(GamepadButton::RightTrigger2),
+                ],
+            ),
+            (
+                ButtonAction::Interact,
+                vec![
+                    ButtonBinding::Key(KeyCode::KeyE),
+                    ButtonBinding::Gamepad(GamepadButton::West),
+                ],
+            ),
+            (
+                ButtonAction::ToggleView,
+                vec![
+                    ButtonBinding::Key(KeyCode::KeyV),
+                    ButtonBinding::Gamepad(GamepadButton::Select),
+                ],
+            ),
+            (
+                ButtonAction::ToggleSpectator,
+                vec![ButtonBinding::Key(KeyCode::KeyN)],
+            ),
+            (
+                ButtonAction::RegenerateDungeon,
+                vec![ButtonBinding::Key(KeyCode::F5)],
+            ),
+        ]);
+
+        let axes = BTreeMap::from([
+            (
+                AxisAction::Move,
+                vec![
+                    AxisBinding::Keys {
+                        up: KeyCode::KeyW,
+                        down: KeyCode::KeyS,
+                        left: KeyCode::KeyA,
+                        right: KeyCode::KeyD,
+                    },
+                    AxisBinding::Keys {
+                        up: KeyCode::ArrowUp,
+                        down: KeyCode::ArrowDown,
+                        left: KeyCode::ArrowLeft,
+                        right: KeyCode::ArrowRight,
+                    },
+                    AxisBinding::GamepadStick {
+                        x: GamepadAxis::LeftStickX,
+                        y: GamepadAxis::LeftStickY,
+                        response: StickResponse::default(),
+                    },
+                ],
+            ),
+            (AxisAction::Look, vec![AxisBinding::MouseMotion]),
+            (
+                AxisAction::Turn,
+                vec![AxisBinding::GamepadStick {
+                    x: GamepadAxis::RightStickX,
+                    y: GamepadAxis::RightStickY,
+                    response: StickResponse {
+                        exponent: 2.0,
+                        ..default()
+                    },
+                }],
+            ),
+        ]);
+
+        Self { buttons, axes }
+    }
+}
+
+/// The state of every logical action this frame, as driven by the [`ActionMap`].
+#[derive(Resource, Default)]
+pub struct ActionState {
+    buttons: ButtonInput<ButtonAction>,
+    axes: HashMap<AxisAction, Vec2>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: ButtonAction) -> bool {
+        self.buttons.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: ButtonAction) -> bool {
+        self.buttons.just_pressed(action)
+    }
+
+    pub fn axis(&self, action: AxisAction) -> Vec2 {
+        self.axes.get(&action).copied().unwrap_or_default()
+    }
+}
+
+fn load_action_map(mut commands: Commands, existing: Option<Res<ActionMap>>) {
+    // Bindings inserted up front, e.g. by tests, take precedence over the config file.
+    if existing.is_some() {
+        return;
+    }
+
+    let mut action_map = settings::load_or_default::<ActionMap>(settings::path(ACTION_MAP_FILE));
+
+    // Actions added since the file was written get their default bindings.
+    let defaults = ActionMap::default();
+    for (action, bindings) in defaults.buttons {
+        action_map.buttons.entry(action).or_insert(bindings);
+    }
+    for (action, bindings) in defaults.axes {
+        action_map.axes.entry(action).or_insert(bindings);
+    }
+
+    commands.insert_resource(action_map);
+}
+
+fn save_action_map(action_map: Res<ActionMap>) {
+    if !action_map.is_changed() || action_map.is_added() {
+        return;
+    }
+
+    let path = settings::path(ACTION_MAP_FILE);
+    if let Err(error) = settings::save(&*action_map, &path) {
+        warn!(
+            "Failed to save input bindings to {}: {error}",
+            path.display()
+        );
+    }
+}
+
+fn update_action_state(
+    action_map: Res<ActionMap>,
+    mut action_state: ResMut<ActionState>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    accumulated_mouse_motion: Res<AccumulatedMouseMotion>,
+    gamepads: Query<&Gamepad>,
+) {
+    let ActionState { buttons, axes } = &mut *action_state;
+
+    buttons.clear();
+    for (&action, bindings) in &action_map.buttons {
+        let pressed = bindings.iter().any(|binding| match binding {
+            ButtonBinding::Key(key) => keyboard_input.pressed(*key),
+            ButtonBinding::Mouse(button) => mouse_input.pressed(*button),
+            ButtonBinding::Gamepad(button) => {
+                gamepads.iter().any(|gamepad| gamepad.pressed(*button))
+            }
+        });
+
+        if pressed {
+            buttons.press(action);
+        } else {
+            buttons.release(action);
+        }
+    }
+
+    for (&action, bindings) in &action_map.axes {
+        let value = bindings
+            .iter()
+            .map(|binding| match binding {
+                AxisBinding::Keys {
+                    up,
+                    down,
+                    left,
+                    right,
+                } => {
+                    let axis = |positive, negative| {
+                        keyboard_input.pressed(positive) as i8
+                            - keyboard_input.pressed(negative) as i8
+                    };
+                    Vec2::new(axis(*right, *left) as f32, axis(*up, *down) as f32)
+                }
+                // Mouse motion points down the screen, so flip it to match the other bindings.
+                AxisBinding::MouseMotion => accumulated_mouse_motion.delta * Vec2::new(1.0, -1.0),
+                AxisBinding::GamepadStick { x, y, response } => gamepads
+                    .iter()
+                    .map(|gamepad| {
+                        response.apply(Vec2::new(
+                            gamepad.get(*x).unwrap_or_default(),
+                            gamepad.get(*y).unwrap_or_default(),
+                        ))
+                    })
+                    .sum(),
+            })
+            .sum();
+
+        axes.insert(action, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{
+        InputPlugin,
+        gamepad::{
+            GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent,
+            RawGamepadButtonChangedEvent, RawGamepadEvent,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn gamepad_events_drive_actions() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, ActionsPlugin))
+            .insert_resource(ActionMap::default());
+
+        let gamepad = app.world_mut().spawn_empty().id();
+        app.world_mut().write_message(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected {
+                name: "Test gamepad".into(),
+                vendor_id: None,
+                product_id: None,
+            },
+        ));
+        app.update();
+
+        for event in [
+            RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                gamepad,
+                GamepadAxis::LeftStickX,
+                1.0,
+            )),
+            // Drift on a resting stick.
+            RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                gamepad,
+                GamepadAxis::RightStickY,
+                0.1,
+            )),
+            RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+                gamepad,
+                GamepadButton::South,
+                1.0,
+            )),
+        ] {
+            app.world_mut().write_message(event);
+        }
+        app.update();
+        app.update();
+
+        let action_state = app.world().resource::<ActionState>();
+        assert!(action_state.pressed(ButtonAction::Jump));
+        assert!(!action_state.pressed(ButtonAction::Crouch));
+        let movement = action_state.axis(AxisAction::Move);
+        assert!((movement - Vec2::X).length() < 1e-3, "{movement}");
+        assert_eq!(action_state.axis(AxisAction::Turn), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,262 @@
+use avian3d::prelude::{Collider, ShapeCastConfig, SpatialQuery, SpatialQueryFilter};
+use bevy::{camera::visibility::RenderLayers, prelude::*, transform::TransformSystems};
+use serde::{Deserialize, Serialize};
+
+use crate::plugins::{
+    actions::{ActionState, ButtonAction},
+    character_controller::EyeOffset,
+    collision::GameLayer,
+    player::{PLAYER_BODY_RENDER_LAYER, Player, ViewModelCamera, WorldModelCamera},
+    settings,
+    spectator::is_spectating,
+};
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostStartup, load_camera_rig)
+            .add_systems(
+                Update,
+                (
+                    toggle_camera_mode.run_if(not(is_spectating)),
+                    apply_field_of_view,
+                ),
+            )
+            // After everything in `Update` has moved the cameras, e.g. for crouching.
+            .add_systems(
+                PostUpdate,
+                update_spring_arm
+                    .run_if(not(is_spectating))
+                    .before(TransformSystems::Propagate),
+            )
+            .add_systems(Last, save_camera_rig);
+    }
+}
+
+/// The settings file the player's camera settings are stored in.
+const CAMERA_RIG_FILE: &str = "camera.ron";
+
+/// How the player's first-person camera responds to look input and what it can see.
+#[derive(Component, Reflect, Debug, Clone, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct CameraRig {
+    /// How far the camera can look down, in radians. Negative values are below the horizon.
+    pub min_pitch: f32,
+    /// How far the camera can look up, in radians.
+    pub max_pitch: f32,
+    /// Time constant of the look smoothing, in seconds. 0 disables it.
+    pub smoothing: f32,
+    /// Extra mouse sensitivity per 1000 pixels per second of mouse speed. 0 disables it.
+    pub acceleration: f32,
+    pub invert_y: bool,
+    /// Vertical field of view of the world camera, in degrees.
+    pub world_fov: f32,
+    /// Vertical field of view of the view model camera, in degrees.
+    pub view_model_fov: f32,
+    /// Camera effects can cause motion sickness, so each can be turned off on its own.
+    pub head_bob: bool,
+    pub landing_dip: bool,
+    pub camera_shake: bool,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            min_pitch: -std::f32::consts::FRAC_PI_3,
+            max_pitch: std::f32::consts::FRAC_PI_3,
+            smoothing: 0.0,
+            acceleration: 0.0,
+            invert_y: false,
+            world_fov: 90.0,
+            view_model_fov: 70.0,
+            head_bob: true,
+            landing_dip: true,
+            camera_shake: true,
+        }
+    }
+}
+
+/// Which view the player's world camera uses.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    ThirdPerson,
+}
+
+/// Pulls the world camera back behind the player in third person, keeping it in front of
+/// any geometry in between.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct SpringArm {
+    pub length: f32,
+    /// Radius of the sphere cast along the arm, which keeps the near plane out of walls.
+    pub probe_radius: f32,
+    /// Eases between first person (0) and third person (1).
+    extension: f32,
+    current_length: f32,
+}
+
+impl SpringArm {
+    pub const fn new(length: f32, probe_radius: f32) -> Self {
+        Self {
+            length,
+            probe_radius,
+            extension: 0.0,
+            current_length: 0.0,
+        }
+    }
+}
+
+impl Default for SpringArm {
+    fn default() -> Self {
+        Self::new(3.5, 0.2)
+    }
+}
+
+/// How quickly the arm extends or retracts when switching camera modes.
+const SPRING_ARM_EXTEND_SPEED: f32 = 6.0;
+
+/// How quickly the arm grows back out once it is no longer blocked.
+const SPRING_ARM_RETURN_SPEED: f32 = 4.0;
+
+/// Below this arm length the camera is inside the player's head, so the body is hidden
+/// and the view model is shown.
+const BODY_VISIBLE_DISTANCE: f32 = 0.5;
+
+fn load_camera_rig(mut rig: Single<&mut CameraRig, With<Player>>) {
+    **rig = settings::load_or_default(settings::path(CAMERA_RIG_FILE));
+}
+
+fn save_camera_rig(rig: Single<Ref<CameraRig>, With<Player>>) {
+    if !rig.is_changed() || rig.is_added() {
+        return;
+    }
+
+    let path = settings::path(CAMERA_RIG_FILE);
+    if let Err(error) = settings::save(&**rig, &path) {
+        warn!(
+            "Failed to save camera settings to {}: {error}",
+            path.display()
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_field_of_view(
+    rig: Single<(&CameraRig, &Children), (With<Player>, Changed<CameraRig>)>,
+    mut cameras: Query<(&mut Projection, Has<WorldModelCamera>, Has<ViewModelCamera>)>,
+) {
+    let (rig, children) = rig.into_inner();
+
+    let mut iter = cameras.iter_many_mut(children);
+    while let Some((mut projection, is_world_model, is_view_model)) = iter.fetch_next() {
+        let Projection::Perspective(perspective) = &mut *projection else {
+            continue;
+        };
+
+        if is_world_model {
+            perspective.fov = rig.world_fov.to_radians();
+        } else if is_view_model {
+            perspective.fov = rig.view_model_fov.to_radians();
+        }
+    }
+}
+
+fn toggle_camera_mode(
+    action_state: Res<ActionState>,
+    mut camera_mode: Single<&mut CameraMode, With<Player>>,
+) {
+    if action_state.just_pressed(ButtonAction::ToggleView) {
+        **camera_mode = match **camera_mode {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::FirstPerson,
+        };
+    }
+}
+
+/// Places the world camera at the end of the spring arm and shows the player's body once
+/// the camera is far enough out of the head.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_spring_arm(
+    time: Res<Time>,
+    spatial_query: SpatialQuery,
+    player: Single<
+        (
+            &Transform,
+            &CameraMode,
+            &mut SpringArm,
+            &EyeOffset,
+            &Children,
+        ),
+        With<Player>,
+    >,
+    mut world_cameras: Query<
+        (&mut Transform, &mut RenderLayers),
+        (With<WorldModelCamera>, Without<Player>),
+    >,
+    mut view_model_cameras: Query<&mut Camera, With<ViewModelCamera>>,
+) {
+    let (player_transform, camera_mode, mut arm, eye_offset, children) = player.into_inner();
+    let delta_time = time.delta_secs();
+
+    let target_extension = match camera_mode {
+        CameraMode::FirstPerson => 0.0,
+        CameraMode::ThirdPerson => 1.0,
+    };
+    arm.extension = arm.extension.lerp(
+        target_extension,
+        1.0 - (-SPRING_ARM_EXTEND_SPEED * delta_time).exp(),
+    );
+    let desired_length = arm.length * arm.extension;
+
+    let mut cameras = world_cameras.iter_many_mut(children);
+    while let Some((mut camera_transform, mut render_layers)) = cameras.fetch_next() {
+        let eye = Vec3::Y * **eye_offset;
+        let backward = camera_transform.rotation * Vec3::Z;
+
+        let origin = player_transform.translation + player_transform.rotation * eye;
+        let allowed_length = Dir3::new(player_transform.rotation * backward)
+            .ok()
+            .filter(|_| desired_length > 0.0)
+            .and_then(|direction| {
+                spatial_query.cast_shape(
+                    &Collider::sphere(arm.probe_radius),
+                    origin,
+                    Quat::IDENTITY,
+                    direction,
+                    &ShapeCastConfig::from_max_distance(desired_length),
+                    &SpatialQueryFilter::from_mask(GameLayer::World),
+                )
+            })
+            .map_or(desired_length, |hit| hit.distance);
+
+        // Snap in immediately so the camera never ends up inside a wall, but ease back out.
+        arm.current_length = if allowed_length < arm.current_length {
+            allowed_length
+        } else {
+            arm.current_length.lerp(
+                allowed_length,
+                1.0 - (-SPRING_ARM_RETURN_SPEED * delta_time).exp(),
+            )
+        };
+
+        camera_transform.translation = eye + backward * arm.current_length;
+
+        let show_body = arm.current_length > BODY_VISIBLE_DISTANCE;
+        render_layers.set_if_neq(if show_body {
+            RenderLayers::default().with(PLAYER_BODY_RENDER_LAYER)
+        } else {
+            RenderLayers::default()
+        });
+
+        for mut view_model_camera in &mut view_model_cameras {
+            if view_model_camera.is_active == show_body {
+                view_model_camera.is_active = !show_body;
+            }
+        }
+    }
+}
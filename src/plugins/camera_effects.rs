@@ -0,0 +1,205 @@
+use avian3d::prelude::LinearVelocity;
+use bevy::{prelude::*, transform::TransformSystems};
+
+use crate::plugins::{
+    camera::{CameraRig, update_spring_arm},
+    character_controller::Grounded,
+    player::{Player, WorldModelCamera},
+};
+
+pub struct CameraEffectsPlugin;
+
+impl Plugin for CameraEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<CameraShake>()
+            // Take last frame's effects back off, so everything else sees the plain camera.
+            .add_systems(PreUpdate, remove_camera_effects)
+            .add_systems(Update, shake_on_hard_landing)
+            .add_systems(
+                PostUpdate,
+                apply_camera_effects
+                    .after(update_spring_arm)
+                    .before(TransformSystems::Propagate),
+            );
+    }
+}
+
+/// Shakes the player's camera. `trauma` adds up to 1, and the shake grows with its square.
+#[derive(Message)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+/// The state of the head bob, landing dip and shake applied on top of the world camera.
+#[derive(Component, Default)]
+pub struct CameraEffects {
+    bob_phase: f32,
+    bob_weight: f32,
+    dip: f32,
+    dip_velocity: f32,
+    trauma: f32,
+    was_grounded: bool,
+    fall_speed: f32,
+    applied_translation: Vec3,
+    applied_rotation: Quat,
+}
+
+/// How far the head bobs up and down at full walking speed, in meters.
+const HEAD_BOB_AMPLITUDE: f32 = 0.04;
+
+/// Bob cycles per meter walked, two steps each.
+const HEAD_BOB_FREQUENCY: f32 = 0.3;
+
+/// Horizontal speed at which the head bob reaches its full amplitude.
+const HEAD_BOB_REFERENCE_SPEED: f32 = 6.0;
+
+/// How quickly the head bob fades in and out when starting and stopping.
+const HEAD_BOB_BLEND_SPEED: f32 = 8.0;
+
+/// Downward dip velocity per meter per second of fall speed when landing.
+const LANDING_DIP_SCALE: f32 = 0.06;
+
+/// Stiffness of the spring that brings the camera back up after a landing dip.
+const LANDING_DIP_STIFFNESS: f32 = 120.0;
+
+/// How much trauma wears off per second.
+const TRAUMA_DECAY: f32 = 1.2;
+
+/// Largest shake rotation at full trauma, in radians, as yaw, pitch and roll.
+const MAX_SHAKE_ANGLE: Vec3 = Vec3::new(0.05, 0.05, 0.08);
+
+/// How fast the shake wobbles.
+const SHAKE_FREQUENCY: f32 = 25.0;
+
+/// Landings faster than this shake the camera, in meters per second.
+const HARD_LANDING_SPEED: f32 = 12.0;
+
+/// Trauma per meter per second of fall speed above [`HARD_LANDING_SPEED`].
+const HARD_LANDING_TRAUMA: f32 = 0.05;
+
+fn shake_on_hard_landing(
+    player: Single<(&LinearVelocity, Has<Grounded>), With<Player>>,
+    mut fall_speed: Local<f32>,
+    mut shake_writer: MessageWriter<CameraShake>,
+) {
+    let (linear_velocity, is_grounded) = player.into_inner();
+
+    if is_grounded {
+        if *fall_speed > HARD_LANDING_SPEED {
+            shake_writer.write(CameraShake {
+                trauma: (*fall_speed - HARD_LANDING_SPEED) * HARD_LANDING_TRAUMA,
+            });
+        }
+        *fall_speed = 0.0;
+    } else {
+        *fall_speed = (-linear_velocity.y).max(0.0);
+    }
+}
+
+fn remove_camera_effects(
+    player: Single<(&mut CameraEffects, &Children), With<Player>>,
+    mut cameras: Query<&mut Transform, With<WorldModelCamera>>,
+) {
+    let (mut effects, children) = player.into_inner();
+
+    let mut cameras = cameras.iter_many_mut(children);
+    while let Some(mut transform) = cameras.fetch_next() {
+        transform.translation -= effects.applied_translation;
+        transform.rotation *= effects.applied_rotation.inverse();
+    }
+
+    effects.applied_translation = Vec3::ZERO;
+    effects.applied_rotation = Quat::IDENTITY;
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_camera_effects(
+    time: Res<Time>,
+    mut shake_reader: MessageReader<CameraShake>,
+    player: Single<
+        (
+            &mut CameraEffects,
+            &CameraRig,
+            &LinearVelocity,
+            Has<Grounded>,
+            &Children,
+        ),
+        With<Player>,
+    >,
+    mut cameras: Query<&mut Transform, With<WorldModelCamera>>,
+) {
+    let (mut effects, rig, linear_velocity, is_grounded, children) = player.into_inner();
+    let delta_time = time.delta_secs();
+
+    // Head bob, proportional to walking speed.
+    let speed = Vec2::new(linear_velocity.x, linear_velocity.z).length();
+    let target_bob_weight = if is_grounded {
+        (speed / HEAD_BOB_REFERENCE_SPEED).min(1.0)
+    } else {
+        0.0
+    };
+    effects.bob_weight = effects.bob_weight.lerp(
+        target_bob_weight,
+        1.0 - (-HEAD_BOB_BLEND_SPEED * delta_time).exp(),
+    );
+    if is_grounded {
+        effects.bob_phase = (effects.bob_phase
+            + speed * HEAD_BOB_FREQUENCY * std::f32::consts::TAU * delta_time)
+            % std::f32::consts::TAU;
+    }
+
+    // Landing dip, kicked by the fall speed and brought back by a critically damped spring.
+    if is_grounded && !effects.was_grounded {
+        effects.dip_velocity -= effects.fall_speed * LANDING_DIP_SCALE;
+    }
+    effects.was_grounded = is_grounded;
+    effects.fall_speed = (-linear_velocity.y).max(0.0);
+
+    let damping = 2.0 * LANDING_DIP_STIFFNESS.sqrt();
+    let acceleration = -LANDING_DIP_STIFFNESS * effects.dip - damping * effects.dip_velocity;
+    effects.dip_velocity += acceleration * delta_time;
+    effects.dip += effects.dip_velocity * delta_time;
+
+    // Trauma based shake.
+    for shake in shake_reader.read() {
+        effects.trauma = (effects.trauma + shake.trauma).clamp(0.0, 1.0);
+    }
+    effects.trauma = (effects.trauma - TRAUMA_DECAY * delta_time).max(0.0);
+
+    let mut translation = Vec3::ZERO;
+    let mut rotation = Quat::IDENTITY;
+
+    if rig.head_bob {
+        translation += Vec3::new(
+            0.5 * effects.bob_phase.sin(),
+            (2.0 * effects.bob_phase).sin(),
+            0.0,
+        ) * HEAD_BOB_AMPLITUDE
+            * effects.bob_weight;
+    }
+
+    if rig.landing_dip {
+        translation.y += effects.dip;
+    }
+
+    if rig.camera_shake && effects.trauma > 0.0 {
+        // Sines at unrelated frequencies stand in for noise.
+        let t = time.elapsed_secs() * SHAKE_FREQUENCY;
+        let wobble = Vec3::new(
+            (t * 1.0).sin() + (t * 2.3).sin() * 0.5,
+            (t * 1.3 + 1.7).sin() + (t * 2.9).sin() * 0.5,
+            (t * 0.7 + 3.1).sin() + (t * 3.7).sin() * 0.5,
+        ) / 1.5;
+        let angles = wobble * MAX_SHAKE_ANGLE * effects.trauma * effects.trauma;
+        rotation = Quat::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z);
+    }
+
+    let mut cameras = cameras.iter_many_mut(children);
+    while let Some(mut transform) = cameras.fetch_next() {
+        transform.translation += translation;
+        transform.rotation *= rotation;
+    }
+
+    effects.applied_translation = translation;
+    effects.applied_rotation = rotation;
+}
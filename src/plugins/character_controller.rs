@@ -1,412 +0,0 @@
-use std::f32::consts::FRAC_PI_4;
-
-use avian3d::{
-    math::*,
-    prelude::{
-        Collider, ColliderOf, Collisions, LinearVelocity, NarrowPhaseSystems, PhysicsSchedule,
-        Position, RigidBody, Rotation, Sensor, ShapeCaster, ShapeHits,
-    },
-};
-use bevy::{input::mouse::AccumulatedMouseMotion, prelude::*};
-
-use crate::plugins::player::{Player, WorldModelCamera};
-
-pub struct CharacterControllerPlugin;
-
-impl Plugin for CharacterControllerPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_message::<MovementAction>()
-            .add_systems(
-                Update,
-                (
-                    mouse_input,
-                    keyboard_input,
-                    update_grounded,
-                    apply_gravity,
-                    movement,
-                    apply_movement_damping,
-                )
-                    .chain(),
-            )
-            .add_systems(
-                // Run collision handling after collision detection.
-                //
-                // NOTE: The collision implementation here is very basic and a bit buggy.
-                //       A collide-and-slide algorithm would likely work better.
-                PhysicsSchedule,
-                kinematic_controller_collisions.in_set(NarrowPhaseSystems::Last),
-            );
-    }
-}
-
-#[derive(Debug, Component, Deref, DerefMut)]
-pub struct CameraSensitivity(Vec2);
-
-impl Default for CameraSensitivity {
-    fn default() -> Self {
-        Self(Vec2::new(0.003, 0.002))
-    }
-}
-
-#[derive(Message)]
-pub enum MovementAction {
-    Move(Vector2),
-    IsCrouching(bool),
-}
-
-#[derive(Component)]
-pub struct CharacterController;
-
-#[derive(Component)]
-#[component(storage = "SparseSet")]
-pub struct Grounded;
-
-#[derive(Component)]
-pub struct MovementAcceleration(Scalar);
-
-#[derive(Component)]
-pub struct MovementDampingFactor(Scalar);
-
-#[derive(Component)]
-pub struct ControllerGravity(Vector);
-
-#[derive(Component)]
-pub struct MaxSlopeAngle(Scalar);
-
-#[derive(Bundle)]
-pub struct CharacterControllerBundle {
-    character_controller: CharacterController,
-    body: RigidBody,
-    collider: Collider,
-    ground_caster: ShapeCaster,
-    gravity: ControllerGravity,
-    movement: MovementBundle,
-}
-
-#[derive(Bundle)]
-pub struct MovementBundle {
-    acceleration: MovementAcceleration,
-    damping: MovementDampingFactor,
-    max_slope_angle: MaxSlopeAngle,
-}
-impl MovementBundle {
-    pub const fn new(accelarion: Scalar, damping: Scalar, max_slope_angle: Scalar) -> Self {
-        Self {
-            acceleration: MovementAcceleration(accelarion),
-            damping: MovementDampingFactor(damping),
-            max_slope_angle: MaxSlopeAngle(max_slope_angle),
-        }
-    }
-}
-impl Default for MovementBundle {
-    fn default() -> Self {
-        Self::new(30.0, 0.9, PI * 0.45)
-    }
-}
-
-impl CharacterControllerBundle {
-    pub fn new(collider: Collider, gravity: Vector) -> Self {
-        let mut caster_shape = collider.clone();
-        caster_shape.set_scale(Vector::ONE * 0.99, 10);
-        Self {
-            character_controller: CharacterController,
-            body: RigidBody::Kinematic,
-            collider,
-            ground_caster: ShapeCaster::new(
-                caster_shape,
-                Vector::ZERO,
-                Quaternion::default(),
-                Dir3::NEG_Y,
-            )
-            .with_max_distance(0.2),
-            gravity: ControllerGravity(gravity),
-            movement: MovementBundle::default(),
-        }
-    }
-
-    pub fn with_movement(
-        mut self,
-        accelarion: Scalar,
-        damping: Scalar,
-        max_slope_angle: Scalar,
-    ) -> Self {
-        self.movement = MovementBundle::new(accelarion, damping, max_slope_angle);
-        self
-    }
-}
-
-fn keyboard_input(
-    mut movement_writer: MessageWriter<MovementAction>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    player: Single<&Transform, With<Player>>,
-) {
-    let up = keyboard_input.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]);
-    let right = keyboard_input.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]);
-    let down = keyboard_input.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]);
-    let left = keyboard_input.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]);
-
-    let forward_input = up as i8 - down as i8;
-    let right_input = right as i8 - left as i8;
-
-    let forward_dir = player.forward();
-    let right_dir = player.right();
-
-    let mut move_direction =
-        (forward_dir * forward_input as f32) + (right_dir * right_input as f32);
-
-    move_direction.y = 0.0;
-    if let Some(normalized_dir) = move_direction.try_normalize() {
-        let direction_2d = Vector2::new(normalized_dir.x, normalized_dir.z);
-        movement_writer.write(MovementAction::Move(direction_2d));
-    }
-
-    if move_direction != Vec3::ZERO {
-        let direction_2d = Vector2::new(move_direction.x, move_direction.z);
-        movement_writer.write(MovementAction::Move(direction_2d));
-    }
-}
-
-fn mouse_input(
-    accumulated_mouse_motion: Res<AccumulatedMouseMotion>,
-    player: Single<(&mut Transform, &CameraSensitivity), With<Player>>,
-    mut camera: Query<&mut Transform, (With<Camera3d>, Without<Player>)>,
-) {
-    let (mut transform, camera_sensitivity) = player.into_inner();
-    let delta = accumulated_mouse_motion.delta;
-
-    if delta != Vec2::ZERO {
-        let delta_yaw = -delta.x * camera_sensitivity.x;
-        let delta_pitch = -delta.y * camera_sensitivity.y;
-
-        let (player_yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
-        let player_new_yaw = player_yaw + delta_yaw;
-
-        transform.rotation = Quat::from_euler(EulerRot::YXZ, player_new_yaw, 0.0, 0.0);
-
-        for mut camera_transform in camera.iter_mut() {
-            let (_, camera_pitch, _) = camera_transform.rotation.to_euler(EulerRot::YXZ);
-            const PITCH_LIMIT: f32 = std::f32::consts::PI / 3.0;
-            let camera_new_pitch = (camera_pitch + delta_pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
-            camera_transform.rotation = Quat::from_euler(EulerRot::YXZ, 0.0, camera_new_pitch, 0.0);
-        }
-    }
-}
-
-fn update_grounded(
-    mut commands: Commands,
-    mut query: Query<
-        (Entity, &ShapeHits, &Rotation, Option<&MaxSlopeAngle>),
-        With<CharacterController>,
-    >,
-) {
-    for (entity, hits, rotation, max_slope_angle) in &mut query {
-        // The character is grounded if the shape caster has a hit with a normal
-        // that isn't too steep.
-        let is_grounded = hits.iter().any(|hit| {
-            if let Some(angle) = max_slope_angle {
-                (rotation * -hit.normal2).angle_between(Vector::Y).abs() <= angle.0
-            } else {
-                true
-            }
-        });
-
-        if is_grounded {
-            commands.entity(entity).insert(Grounded);
-        } else {
-            commands.entity(entity).remove::<Grounded>();
-        }
-    }
-}
-
-/// Responds to [`MovementAction`] events and moves character controllers accordingly.
-fn movement(
-    time: Res<Time>,
-    mut movement_reader: MessageReader<MovementAction>,
-    mut controllers: Query<(&MovementAcceleration, &mut LinearVelocity, Has<Grounded>)>,
-) {
-    // Precision is adjusted so that the example works with
-    // both the `f32` and `f64` features. Otherwise you don't need this.
-    let delta_time = time.delta_secs_f64().adjust_precision();
-
-    for event in movement_reader.read() {
-        for (movement_acceleration, mut linear_velocity, is_grounded) in &mut controllers {
-            match event {
-                MovementAction::Move(direction) => {
-                    linear_velocity.x += direction.x * movement_acceleration.0 * delta_time;
-                    linear_velocity.z += direction.y * movement_acceleration.0 * delta_time;
-                }
-                MovementAction::IsCrouching(true) => {
-                    if is_grounded {
-                        todo!()
-                    }
-                }
-                MovementAction::IsCrouching(false) => {
-                    if is_grounded {
-                        todo!()
-                    }
-                }
-            }
-        }
-    }
-}
-
-fn apply_movement_damping(mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>) {
-    for (damping_factor, mut linear_velocity) in &mut query {
-        // We could use `LinearDamping`, but we don't want to dampen movement along the Y axis
-        linear_velocity.x *= damping_factor.0;
-        linear_velocity.z *= damping_factor.0;
-    }
-}
-
-fn apply_gravity(
-    time: Res<Time>,
-    mut controllers: Query<(&ControllerGravity, &mut LinearVelocity)>,
-) {
-    // Precision is adjusted so that the example works with
-    // both the `f32` and `f64` features. Otherwise you don't need this.
-    let delta_time = time.delta_secs_f64().adjust_precision();
-
-    for (gravity, mut linear_velocity) in &mut controllers {
-        linear_velocity.0 += gravity.0 * delta_time;
-    }
-}
-
-#[allow(clippy::type_complexity)]
-fn kinematic_controller_collisions(
-    collisions: Collisions,
-    bodies: Query<&RigidBody>,
-    collider_rbs: Query<&ColliderOf, Without<Sensor>>,
-    mut character_controllers: Query<
-        (&mut Position, &mut LinearVelocity, Option<&MaxSlopeAngle>),
-        (With<RigidBody>, With<CharacterController>),
-    >,
-    time: Res<Time>,
-) {
-    for contacts in collisions.iter() {
-        let Ok([&ColliderOf { body: rb1 }, &ColliderOf { body: rb2 }]) =
-            collider_rbs.get_many([contacts.collider1, contacts.collider2])
-        else {
-            continue;
-        };
-
-        let is_first: bool;
-
-        let character_rb: RigidBody;
-        let is_other_dynamic: bool;
-
-        let (mut position, mut linear_velocity, max_slope_angle) =
-            if let Ok(character) = character_controllers.get_mut(rb1) {
-                is_first = true;
-                character_rb = *bodies.get(rb1).unwrap();
-                is_other_dynamic = bodies.get(rb2).is_ok_and(|rb| rb.is_dynamic());
-                character
-            } else if let Ok(character) = character_controllers.get_mut(rb2) {
-                is_first = false;
-                character_rb = *bodies.get(rb2).unwrap();
-                is_other_dynamic = bodies.get(rb1).is_ok_and(|rb| rb.is_dynamic());
-                character
-            } else {
-                continue;
-            };
-
-        if !character_rb.is_kinematic() {
-            continue;
-        }
-        for manifold in contacts.manifolds.iter() {
-            let normal = if is_first {
-                -manifold.normal
-            } else {
-                manifold.normal
-            };
-
-            let mut deepest_penetration: Scalar = Scalar::MIN;
-
-            // Solve each penetrating contact in the manifold.
-            for contact in manifold.points.iter() {
-                if contact.penetration > 0.0 {
-                    position.0 += normal * contact.penetration;
-                }
-                deepest_penetration = deepest_penetration.max(contact.penetration);
-            }
-
-            // For now, this system only handles velocity corrections for collisions against static geometry.
-            if is_other_dynamic {
-                continue;
-            }
-
-            let slope_angle = normal.angle_between(Vector::Y);
-            let climbable = max_slope_angle.is_some_and(|angle| slope_angle.abs() <= angle.0);
-
-            if deepest_penetration > 0.0 {
-                // If the slope is climbable, snap the velocity so that the character
-                // up and down the surface smoothly.
-                if climbable {
-                    // Points in the normal's direction in the XZ plane.
-                    let normal_direction_xz =
-                        normal.reject_from_normalized(Vector::Y).normalize_or_zero();
-
-                    // The movement speed along the direction above.
-                    let linear_velocity_xz = linear_velocity.dot(normal_direction_xz);
-
-                    // Snap the Y speed based on the speed at which the character is moving
-                    // up or down the slope, and how steep the slope is.
-                    //
-                    // A 2D visualization of the slope, the contact normal, and the velocity components:
-                    //
-                    //             ╱
-                    //     normal ╱
-                    // *         ╱
-                    // │   *    ╱   velocity_x
-                    // │       * - - - - - -
-                    // │           *       | velocity_y
-                    // │               *   |
-                    // *───────────────────*
-
-                    let max_y_speed = -linear_velocity_xz * slope_angle.tan();
-                    linear_velocity.y = linear_velocity.y.max(max_y_speed);
-                } else {
-                    // The character is intersecting an unclimbable object, like a wall.
-                    // We want the character to slide along the surface, similarly to
-                    // a collide-and-slide algorithm.
-
-                    // Don't apply an impulse if the character is moving away from the surface.
-                    if linear_velocity.dot(normal) > 0.0 {
-                        continue;
-                    }
-
-                    // Slide along the surface, rejecting the velocity along the contact normal.
-                    let impulse = linear_velocity.reject_from_normalized(normal);
-                    linear_velocity.0 = impulse;
-                }
-            } else {
-                // The character is not yet intersecting the other object,
-                // but the narrow phase detected a speculative collision.
-                //
-                // We need to push back the part of the velocity
-                // that would cause penetration within the next frame.
-
-                let normal_speed = linear_velocity.dot(normal);
-
-                // Don't apply an impulse if the character is moving away from the surface.
-                if normal_speed > 0.0 {
-                    continue;
-                }
-
-                // Compute the impulse to apply.
-                let impulse_magnitude =
-                    normal_speed - (deepest_penetration / time.delta_secs_f64().adjust_precision());
-                let mut impulse = impulse_magnitude * normal;
-
-                // Apply the impulse differently depending on the slope angle.
-                if climbable {
-                    // Avoid sliding down slopes.
-                    linear_velocity.y -= impulse.y.min(0.0);
-                } else {
-                    // Avoid climbing up walls.
-                    impulse.y = impulse.y.max(0.0);
-                    linear_velocity.0 -= impulse;
-                }
-            }
-        }
-    }
-}
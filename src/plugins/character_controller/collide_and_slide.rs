@@ -0,0 +1,593 @@
+use avian3d::{
+    math::*,
+    prelude::{
+        Collider, ColliderOf, CollisionLayers, Collisions, ComputedMass, Forces, LinearVelocity,
+        Position, RigidBody, RigidBodyForces, Rotation, Sensor, ShapeCastConfig, ShapeHitData,
+        SpatialQuery, SpatialQueryFilter,
+    },
+};
+use bevy::prelude::*;
+
+use super::{
+    CharacterController, CharacterMass, GroundSnapDistance, Grounded, MaxSlopeAngle, MaxStepHeight,
+    PushStrength, platforms::PlatformMotion,
+};
+
+/// How many times the remaining motion can be redirected along a surface in a single step.
+const MAX_SLIDE_ITERATIONS: usize = 4;
+
+/// Gap kept between the character and the surfaces it slides along,
+/// so that the next cast doesn't start out touching them.
+const SKIN_WIDTH: Scalar = 0.01;
+
+/// The velocity a kinematic character keeps between physics steps.
+///
+/// [`collide_and_slide`] swaps [`LinearVelocity`] for the displacement the character can actually
+/// cover this step, and [`restore_velocity`] puts this back once the solver has moved the body.
+#[derive(Component, Default)]
+pub(super) struct SlideVelocity(Vector);
+
+/// Moves kinematic character controllers along their velocity, sliding along anything they hit.
+///
+/// The solver still integrates the velocity of kinematic bodies, so instead of moving the
+/// character directly, the velocity is replaced with the displacement it can actually cover
+/// this step. Because the whole displacement is shape-cast, fast characters can't tunnel
+/// through thin geometry.
+#[allow(clippy::type_complexity)]
+pub(super) fn collide_and_slide(
+    time: Res<Time>,
+    spatial_query: SpatialQuery,
+    mut controllers: Query<
+        (
+            Entity,
+            &RigidBody,
+            &Collider,
+            &CollisionLayers,
+            &Position,
+            &Rotation,
+            &mut LinearVelocity,
+            &mut SlideVelocity,
+            Option<&MaxSlopeAngle>,
+            Option<&MaxStepHeight>,
+            Option<&GroundSnapDistance>,
+            Option<(&CharacterMass, &PushStrength)>,
+            Option<&PlatformMotion>,
+            Has<Grounded>,
+        ),
+        With<CharacterController>,
+    >,
+    collider_rbs: Query<&ColliderOf, Without<Sensor>>,
+    mut dynamic_bodies: Query<(&RigidBody, &ComputedMass, Forces), Without<CharacterController>>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+    if delta_time <= 0.0 {
+        return;
+    }
+
+    for (
+        entity,
+        rb,
+        collider,
+        collision_layers,
+        position,
+        rotation,
+        mut linear_velocity,
+        mut slide_velocity,
+        max_slope_angle,
+        max_step_height,
+        ground_snap,
+        push,
+        platform_motion,
+        is_grounded,
+    ) in &mut controllers
+    {
+        if !rb.is_kinematic() {
+            continue;
+        }
+
+        let mut sweep = Sweep {
+            spatial_query: &spatial_query,
+            filter: SpatialQueryFilter::from_mask(collision_layers.filters)
+                .with_excluded_entities([entity]),
+            collider,
+            rotation,
+            max_slope_angle: max_slope_angle.map(|angle| angle.0),
+            position: position.0,
+            velocity: linear_velocity.0,
+            impacts: Vec::new(),
+        };
+
+        // Steps are only taken while walking, not while jumping or falling.
+        let step_height = max_step_height
+            .filter(|_| is_grounded)
+            .map_or(0.0, |height| height.0);
+
+        if let Some(PlatformMotion {
+            ground: Some(platform),
+            linear,
+        }) = platform_motion
+        {
+            sweep.carry(*linear * delta_time, *platform);
+        }
+        sweep.slide(linear_velocity.0 * delta_time, step_height);
+
+        // Stay attached to the ground unless the character is jumping off it.
+        if is_grounded && sweep.velocity.y <= 0.0 {
+            let snap_distance = ground_snap.map_or(0.0, |snap| snap.0).max(step_height);
+            sweep.snap_down(snap_distance);
+        }
+
+        if let Some((mass, push_strength)) = push {
+            for (hit, velocity) in &sweep.impacts {
+                let Ok(&ColliderOf { body }) = collider_rbs.get(hit.entity) else {
+                    continue;
+                };
+                let Ok((rb, body_mass, mut forces)) = dynamic_bodies.get_mut(body) else {
+                    continue;
+                };
+                if !rb.is_dynamic() {
+                    continue;
+                }
+
+                // Treat the push as an inelastic collision along the contact normal,
+                // so light bodies are shoved aside and heavy ones barely budge.
+                let approach_speed = (*velocity - forces.linear_velocity()).dot(-hit.normal1);
+                if approach_speed <= 0.0 {
+                    continue;
+                }
+                let reduced_mass = mass.0 * body_mass.value() / (mass.0 + body_mass.value());
+                let impulse = -hit.normal1 * approach_speed * reduced_mass * push_strength.0;
+                forces.apply_linear_impulse_at_point(impulse, hit.point1);
+            }
+        }
+
+        slide_velocity.0 = sweep.velocity;
+        linear_velocity.0 = (sweep.position - position.0) / delta_time;
+    }
+}
+
+/// Puts back the velocity stashed by [`collide_and_slide`] after the solver has moved the body.
+pub(super) fn restore_velocity(
+    mut controllers: Query<(&RigidBody, &SlideVelocity, &mut LinearVelocity)>,
+) {
+    for (rb, slide_velocity, mut linear_velocity) in &mut controllers {
+        if rb.is_kinematic() {
+            linear_velocity.0 = slide_velocity.0;
+        }
+    }
+}
+
+/// A character collider being swept through the world during a single physics step.
+struct Sweep<'a, 'w, 's> {
+    spatial_query: &'a SpatialQuery<'w, 's>,
+    filter: SpatialQueryFilter,
+    collider: &'a Collider,
+    rotation: &'a Rotation,
+    max_slope_angle: Option<Scalar>,
+    position: Vector,
+    /// The character's velocity, clipped against every surface hit so far.
+    velocity: Vector,
+    /// Every surface hit so far, along with the character's velocity right before hitting it.
+    impacts: Vec<(ShapeHitData, Vector)>,
+}
+
+impl Sweep<'_, '_, '_> {
+    fn cast(&self, origin: Vector, direction: Dir3, distance: Scalar) -> Option<ShapeHitData> {
+        let config = ShapeCastConfig {
+            max_distance: distance + SKIN_WIDTH,
+            ignore_origin_penetration: true,
+            ..default()
+        };
+        self.spatial_query.cast_shape(
+            self.collider,
+            origin,
+            self.rotation.0,
+            direction,
+            &config,
+            &self.filter,
+        )
+    }
+
+    fn is_walkable(&self, normal: Vector) -> bool {
+        self.max_slope_angle
+            .is_some_and(|angle| normal.angle_between(Vector::Y) <= angle)
+    }
+
+    /// Moves along `motion`, sliding along each surface hit and along the crease
+    /// between two surfaces. Obstacles up to `step_height` tall are stepped onto.
+    fn slide(&mut self, motion: Vector, step_height: Scalar) {
+        let mut remaining = motion;
+        let mut planes: Vec<Vector> = Vec::with_capacity(MAX_SLIDE_ITERATIONS);
+
+        for _ in 0..MAX_SLIDE_ITERATIONS {
+            let Ok((direction, distance)) = Dir3::new_and_length(remaining) else {
+                break;
+            };
+
+            let Some(hit) = self.cast(self.position, direction, distance) else {
+                self.position += remaining;
+                break;
+            };
+
+            // Move up to the surface, leaving a small gap.
+            let travel = (hit.distance - SKIN_WIDTH).max(0.0);
+            self.position += direction * travel;
+            remaining = direction * (distance - travel);
+
+            let normal = hit.normal1;
+            let walkable = self.is_walkable(normal);
+
+            if !walkable && step_height > 0.0 {
+                let horizontal = Vector::new(remaining.x, 0.0, remaining.z);
+                if let Some(stepped) = self.step_up(horizontal, step_height) {
+                    remaining = stepped;
+                    self.velocity.y = self.velocity.y.max(0.0);
+                    continue;
+                }
+            }
+
+            self.impacts.push((hit, self.velocity));
+            remaining = clip_motion(remaining, normal, walkable);
+            self.velocity = clip_motion(self.velocity, normal, walkable);
+
+            // If sliding along this surface pushes the character back into a previous one,
+            // it is stuck in a crease and can only move along the line where they meet.
+            if let Some(&previous) = planes.iter().find(|plane| remaining.dot(**plane) < 0.0) {
+                let crease = previous.cross(normal).normalize_or_zero();
+                remaining = crease * remaining.dot(crease);
+                self.velocity = crease * self.velocity.dot(crease);
+
+                // Three or more surfaces boxing the character in; there is nowhere to go.
+                if planes
+                    .iter()
+                    .any(|plane| remaining.dot(*plane) < -Scalar::EPSILON)
+                {
+                    self.velocity = Vector::ZERO;
+                    break;
+                }
+            }
+
+            planes.push(normal);
+        }
+    }
+
+    /// Tries to climb onto an obstacle by moving up, across and back down onto walkable ground.
+    /// Returns the horizontal motion left over if the step succeeded.
+    fn step_up(&mut self, horizontal: Vector, step_height: Scalar) -> Option<Vector> {
+        let (direction, distance) = Dir3::new_and_length(horizontal).ok()?;
+
+        let rise = self
+            .cast(self.position, Dir3::Y, step_height)
+            .map_or(step_height, |hit| (hit.distance - SKIN_WIDTH).max(0.0));
+        if rise <= SKIN_WIDTH {
+            return None;
+        }
+        let raised = self.position + Vector::Y * rise;
+
+        let advance = self
+            .cast(raised, direction, distance)
+            .map_or(distance, |hit| (hit.distance - SKIN_WIDTH).max(0.0));
+        if advance <= SKIN_WIDTH {
+            return None;
+        }
+        let advanced = raised + direction * advance;
+
+        // There has to be walkable ground to stand on at the top of the step.
+        let hit = self.cast(advanced, Dir3::NEG_Y, rise)?;
+        if !self.is_walkable(hit.normal1) {
+            return None;
+        }
+
+        self.position = advanced - Vector::Y * (hit.distance - SKIN_WIDTH).max(0.0);
+        Some(direction * (distance - advance))
+    }
+
+    /// Moves the character along with the platform it is standing on. The platform itself
+    /// is ignored, since the solver hasn't moved it yet this step.
+    fn carry(&mut self, motion: Vector, platform: Entity) {
+        let Ok((direction, distance)) = Dir3::new_and_length(motion) else {
+            return;
+        };
+
+        self.filter.excluded_entities.insert(platform);
+        let travel = self
+            .cast(self.position, direction, distance)
+            .map_or(distance, |hit| (hit.distance - SKIN_WIDTH).max(0.0));
+        self.filter.excluded_entities.remove(&platform);
+
+        self.position += direction * travel;
+    }
+
+    /// Keeps the character on the ground when walking down steps and slopes,
+    /// instead of falling off every ledge.
+    fn snap_down(&mut self, distance: Scalar) {
+        let Some(hit) = self.cast(self.position, Dir3::NEG_Y, distance) else {
+            return;
+        };
+        if !self.is_walkable(hit.normal1) {
+            return;
+        }
+
+        self.position.y -= (hit.distance - SKIN_WIDTH).max(0.0);
+        self.velocity = clip_motion(self.velocity, hit.normal1, true);
+    }
+}
+
+/// Removes the part of `motion` that goes into a surface with the given normal.
+fn clip_motion(motion: Vector, normal: Vector, walkable: bool) -> Vector {
+    if motion.dot(normal) >= 0.0 {
+        return motion;
+    }
+
+    if walkable {
+        // Follow the slope in the direction the character is moving horizontally,
+        // rather than sliding down it.
+        let horizontal = Vector::new(motion.x, 0.0, motion.z);
+        return Vector::new(motion.x, -horizontal.dot(normal) / normal.y, motion.z);
+    }
+
+    // Walls and steep slopes only block horizontal movement, so the character can't climb them.
+    let wall_normal = Vector::new(normal.x, 0.0, normal.z).normalize_or_zero();
+    let clipped = motion - wall_normal * motion.dot(wall_normal).min(0.0);
+
+    // Still moving into the surface, for example when falling onto a steep slope.
+    if clipped.dot(normal) < 0.0 {
+        clipped.reject_from_normalized(normal)
+    } else {
+        clipped
+    }
+}
+
+/// Pushes kinematic character controllers out of anything they end up overlapping,
+/// such as bodies that moved into them. Velocity is left to [`collide_and_slide`].
+#[allow(clippy::type_complexity)]
+pub(super) fn depenetrate(
+    collisions: Collisions,
+    bodies: Query<&RigidBody>,
+    collider_rbs: Query<&ColliderOf, Without<Sensor>>,
+    mut character_controllers: Query<&mut Position, (With<RigidBody>, With<CharacterController>)>,
+) {
+    for contacts in collisions.iter() {
+        let Ok([&ColliderOf { body: rb1 }, &ColliderOf { body: rb2 }]) =
+            collider_rbs.get_many([contacts.collider1, contacts.collider2])
+        else {
+            continue;
+        };
+
+        let (is_first, character_rb) = if character_controllers.contains(rb1) {
+            (true, rb1)
+        } else if character_controllers.contains(rb2) {
+            (false, rb2)
+        } else {
+            continue;
+        };
+
+        if !bodies.get(character_rb).is_ok_and(|rb| rb.is_kinematic()) {
+            continue;
+        }
+
+        let Ok(mut position) = character_controllers.get_mut(character_rb) else {
+            continue;
+        };
+
+        for manifold in contacts.manifolds.iter() {
+            let normal = if is_first {
+                -manifold.normal
+            } else {
+                manifold.normal
+            };
+
+            // Solve each penetrating contact in the manifold.
+            for contact in manifold.points.iter() {
+                if contact.penetration > 0.0 {
+                    position.0 += normal * contact.penetration;
+                }
+            }
+        }
+    }
+}
+
+/// Knocks character controllers back when a dynamic body runs into them,
+/// scaled by how heavy the body is compared to the character.
+#[allow(clippy::type_complexity)]
+pub(super) fn apply_knockback(
+    collisions: Collisions,
+    collider_rbs: Query<&ColliderOf, Without<Sensor>>,
+    dynamic_bodies: Query<
+        (&RigidBody, &ComputedMass, &LinearVelocity),
+        Without<CharacterController>,
+    >,
+    mut character_controllers: Query<
+        (&RigidBody, &CharacterMass, &mut SlideVelocity),
+        With<CharacterController>,
+    >,
+) {
+    for contacts in collisions.iter() {
+        let Ok([&ColliderOf { body: rb1 }, &ColliderOf { body: rb2 }]) =
+            collider_rbs.get_many([contacts.collider1, contacts.collider2])
+        else {
+            continue;
+        };
+
+        let (is_first, character_rb, other_rb) = if character_controllers.contains(rb1) {
+            (true, rb1, rb2)
+        } else if character_controllers.contains(rb2) {
+            (false, rb2, rb1)
+        } else {
+            continue;
+        };
+
+        let Ok((other_body, other_mass, other_velocity)) = dynamic_bodies.get(other_rb) else {
+            continue;
+        };
+        let Ok((character_body, character_mass, mut slide_velocity)) =
+            character_controllers.get_mut(character_rb)
+        else {
+            continue;
+        };
+        if !other_body.is_dynamic() || !character_body.is_kinematic() {
+            continue;
+        }
+
+        let Some(manifold) = contacts.manifolds.first() else {
+            continue;
+        };
+        // Points from the other body towards the character.
+        let normal = if is_first {
+            -manifold.normal
+        } else {
+            manifold.normal
+        };
+
+        let approach_speed = (other_velocity.0 - slide_velocity.0).dot(normal);
+        if approach_speed <= 0.0 {
+            continue;
+        }
+
+        // The impulse that stops the two closing in on each other, as if they stuck together.
+        let reduced_mass =
+            other_mass.value() * character_mass.0 / (other_mass.value() + character_mass.0);
+        slide_velocity.0 += character_mass.velocity_change(normal * approach_speed * reduced_mass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use avian3d::prelude::{Gravity, Mass};
+
+    use super::super::tests::{app, intent, position, run, spawn_box, spawn_controller};
+    use super::*;
+
+    /// The closest the center of the player sized test capsule can get to a wall.
+    const RADIUS: Scalar = 0.4;
+
+    fn spawn_wall(app: &mut App, center: Vector, size: Vector) {
+        spawn_box(app, RigidBody::Static, center, size);
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let mut app = app();
+        spawn_wall(&mut app, Vector::X * 2.5, Vector::new(1.0, 4.0, 40.0));
+        let character = spawn_controller(&mut app, Vector::NEG_Y, Vector::ZERO);
+        intent(&mut app, character).direction = Vector2::ONE;
+
+        run(&mut app, 2.0);
+        let position = position(&app, character);
+        assert!(position.x <= 2.0 - RADIUS + SKIN_WIDTH, "{position}");
+        assert!(position.x > 2.0 - RADIUS - 0.1, "{position}");
+        assert!(position.z > 3.0, "{position}");
+    }
+
+    #[test]
+    fn stops_in_corners() {
+        let mut app = app();
+        spawn_wall(&mut app, Vector::X * 2.5, Vector::new(1.0, 4.0, 10.0));
+        spawn_wall(&mut app, Vector::Z * 2.5, Vector::new(10.0, 4.0, 1.0));
+        let character = spawn_controller(&mut app, Vector::NEG_Y, Vector::ZERO);
+        intent(&mut app, character).direction = Vector2::ONE;
+
+        run(&mut app, 2.0);
+        let position = position(&app, character);
+        for coordinate in [position.x, position.z] {
+            assert!(coordinate <= 2.0 - RADIUS + SKIN_WIDTH, "{position}");
+            assert!(coordinate > 2.0 - RADIUS - 0.1, "{position}");
+        }
+        let velocity = app.world().get::<LinearVelocity>(character).unwrap().0;
+        assert!(velocity.length() < 0.1, "{velocity}");
+    }
+
+    /// Launches a character at `speed` towards a wall of the given thickness whose near face is
+    /// at `x = 3`, and returns how far it got.
+    fn launch_at_wall(speed: Scalar, thickness: Scalar) -> Scalar {
+        let mut app = app();
+        spawn_wall(
+            &mut app,
+            Vector::X * (3.0 + thickness * 0.5),
+            Vector::new(thickness, 4.0, 4.0),
+        );
+        let character = spawn_controller(&mut app, Vector::NEG_Y, Vector::ZERO);
+        app.world_mut()
+            .get_mut::<LinearVelocity>(character)
+            .unwrap()
+            .0 = Vector::X * speed;
+
+        run(&mut app, 0.5);
+        position(&app, character).x
+    }
+
+    #[test]
+    fn does_not_pass_through_thin_walls() {
+        // Over twice the width of the capsule per tick, so checking for overlaps after
+        // each step would miss the wall entirely.
+        let x = launch_at_wall(120.0, 0.02);
+        assert!(x <= 3.0 - RADIUS + SKIN_WIDTH, "{x}");
+    }
+
+    #[test]
+    fn does_not_tunnel_at_high_speed() {
+        let x = launch_at_wall(2000.0, 1.0);
+        assert!(x <= 3.0 - RADIUS + SKIN_WIDTH, "{x}");
+    }
+
+    /// A weightless app with a character centered on the origin and a unit crate of the
+    /// given mass centered at `crate_x`.
+    fn character_and_crate(mass: Scalar, crate_x: Scalar) -> (App, Entity, Entity) {
+        let mut app = app();
+        app.insert_resource(Gravity(Vector::ZERO));
+        let character = spawn_controller(&mut app, Vector::NEG_Y * 0.95, Vector::ZERO);
+        let crate_body = spawn_box(
+            &mut app,
+            RigidBody::Dynamic,
+            Vector::X * crate_x,
+            Vector::ONE,
+        );
+        app.world_mut().entity_mut(crate_body).insert(Mass(mass));
+        (app, character, crate_body)
+    }
+
+    /// Runs a character into a crate at 4 m/s, and returns how fast the crate moves after.
+    fn push_crate(mass: Scalar) -> Scalar {
+        let (mut app, character, crate_body) = character_and_crate(mass, 1.0);
+        app.world_mut()
+            .get_mut::<LinearVelocity>(character)
+            .unwrap()
+            .0 = Vector::X * 4.0;
+
+        run(&mut app, 0.5);
+        app.world().get::<LinearVelocity>(crate_body).unwrap().x
+    }
+
+    #[test]
+    fn pushes_light_bodies_harder_than_heavy_ones() {
+        let (light, heavy) = (push_crate(5.0), push_crate(500.0));
+        assert!(light > 2.0, "{light} m/s");
+        assert!(heavy > 0.0, "{heavy} m/s");
+        assert!(
+            heavy < light * 0.25,
+            "{light} m/s at 5 kg, {heavy} m/s at 500 kg"
+        );
+    }
+
+    /// Throws a crate at an idle character at 5 m/s, and returns how far it is knocked back.
+    fn throw_crate(mass: Scalar) -> Scalar {
+        let (mut app, character, crate_body) = character_and_crate(mass, -2.0);
+        app.world_mut()
+            .get_mut::<LinearVelocity>(crate_body)
+            .unwrap()
+            .0 = Vector::X * 5.0;
+
+        run(&mut app, 1.0);
+        position(&app, character).x
+    }
+
+    #[test]
+    fn heavy_bodies_knock_characters_back_further() {
+        let (light, heavy) = (throw_crate(20.0), throw_crate(320.0));
+        assert!(light > 0.05, "{light} m");
+        assert!(
+            heavy > light * 2.0,
+            "{light} m at 20 kg, {heavy} m at 320 kg"
+        );
+    }
+}
@@ -0,0 +1,1374 @@
+mod collide_and_slide;
+mod platforms;
+
+use avian3d::{
+    math::*,
+    prelude::{
+        Collider, CollisionLayers, LinearVelocity, NarrowPhaseSystems, PhysicsSchedule,
+        PhysicsStepSystems, Position, RigidBody, Rotation, ShapeCastConfig, ShapeCaster, ShapeHits,
+        SimpleCollider, SpatialQuery, SpatialQueryFilter, TranslationInterpolation,
+    },
+};
+use bevy::prelude::*;
+
+use crate::plugins::{
+    actions::{ActionState, AxisAction, ButtonAction},
+    camera::CameraRig,
+    cursor::CursorState,
+    player::Player,
+    spectator::is_spectating,
+};
+use collide_and_slide::{
+    SlideVelocity, apply_knockback, collide_and_slide, depenetrate, restore_velocity,
+};
+pub use platforms::PlatformMotion;
+use platforms::track_platforms;
+
+pub struct CharacterControllerPlugin;
+
+impl Plugin for CharacterControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<MovementMessage>()
+            .add_message::<StaminaExhausted>()
+            .add_message::<StaminaRecovered>()
+            .add_systems(
+                Update,
+                (look_input.run_if(not(is_spectating)), update_eye_offset),
+            )
+            // Simulate on the fixed timestep, right before physics runs in `FixedPostUpdate`,
+            // so that movement doesn't depend on the frame rate.
+            .add_systems(
+                FixedUpdate,
+                (
+                    movement_input.run_if(not(is_spectating)),
+                    update_grounded,
+                    track_platforms,
+                    apply_gravity,
+                    movement,
+                    apply_jump,
+                    update_stamina,
+                    apply_movement_damping,
+                )
+                    .chain()
+                    .in_set(CharacterControllerSystems),
+            )
+            .add_systems(
+                PhysicsSchedule,
+                (
+                    // Sweep the character before the solver integrates its velocity.
+                    collide_and_slide.in_set(PhysicsStepSystems::First),
+                    // Resolve any remaining overlaps after collision detection.
+                    (depenetrate, apply_knockback).in_set(NarrowPhaseSystems::Last),
+                    restore_velocity.in_set(PhysicsStepSystems::Last),
+                ),
+            );
+    }
+}
+
+/// The fixed timestep systems that move character controllers. Anything that sends
+/// [`MovementMessage`]s should run before them.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CharacterControllerSystems;
+
+#[derive(Debug, Component, Deref, DerefMut)]
+pub struct CameraSensitivity(Vec2);
+
+impl Default for CameraSensitivity {
+    fn default() -> Self {
+        Self(Vec2::new(0.003, 0.002))
+    }
+}
+
+/// How fast a fully pushed look stick turns the camera, in radians per second.
+#[derive(Debug, Component, Deref, DerefMut)]
+pub struct StickSensitivity(Vec2);
+
+impl Default for StickSensitivity {
+    fn default() -> Self {
+        Self(Vec2::new(3.5, 2.5))
+    }
+}
+
+/// A [`MovementAction`] addressed to a single character controller.
+#[derive(Message)]
+pub struct MovementMessage {
+    pub entity: Entity,
+    pub action: MovementAction,
+}
+
+impl MovementMessage {
+    pub const fn new(entity: Entity, action: MovementAction) -> Self {
+        Self { entity, action }
+    }
+}
+
+pub enum MovementAction {
+    /// Sent once per tick. `direction` is a horizontal unit vector, or zero when idle,
+    /// and `magnitude` in `[0, 1]` scales the acceleration for analog input.
+    Move {
+        direction: Vector2,
+        magnitude: Scalar,
+    },
+    IsCrouching(bool),
+    IsSprinting(bool),
+    Jump,
+    JumpReleased,
+    /// An impulse, e.g. from a hit, that knocks the character back less the heavier it is.
+    Knockback(Vector),
+}
+
+#[derive(Component)]
+pub struct CharacterController;
+
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Grounded;
+
+#[derive(Component)]
+pub struct MovementAcceleration(Scalar);
+
+/// The fraction of horizontal velocity kept every 1/60th of a second.
+#[derive(Component)]
+pub struct MovementDampingFactor(Scalar);
+
+/// The rate [`MovementDampingFactor`] is expressed at, so damping doesn't depend on the timestep.
+const DAMPING_REFERENCE_RATE: Scalar = 60.0;
+
+#[derive(Component)]
+pub struct ControllerGravity(Vector);
+
+#[derive(Component, Deref)]
+pub struct MaxSlopeAngle(Scalar);
+
+/// How far below a grounded character the ground can drop away before it starts falling.
+///
+/// Keeps the character attached when running down slopes and over small bumps.
+#[derive(Component)]
+pub struct GroundSnapDistance(Scalar);
+
+/// The walkable surface a character is standing on, kept up to date by `update_grounded`.
+#[derive(Component)]
+pub struct GroundInfo {
+    pub normal: Vector,
+    pub slope_angle: Scalar,
+    /// `None` while the character is airborne.
+    pub entity: Option<Entity>,
+}
+
+impl Default for GroundInfo {
+    fn default() -> Self {
+        Self {
+            normal: Vector::Y,
+            slope_angle: 0.0,
+            entity: None,
+        }
+    }
+}
+
+/// How heavy the character is when pushing or being pushed by dynamic bodies.
+#[derive(Component)]
+pub struct CharacterMass(Scalar);
+
+impl CharacterMass {
+    /// How much an `impulse` changes the character's velocity.
+    pub fn velocity_change(&self, impulse: Vector) -> Vector {
+        impulse / self.0
+    }
+}
+
+/// Scales the impulses a character applies to the dynamic bodies it walks into.
+#[derive(Component)]
+pub struct PushStrength(Scalar);
+
+/// The tallest ledge the character can walk onto or down from without jumping or falling.
+#[derive(Component, Deref)]
+pub struct MaxStepHeight(Scalar);
+
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Crouched;
+
+/// Colliders and speed used when the character crouches.
+#[derive(Component)]
+pub struct CrouchSettings {
+    standing_collider: Collider,
+    crouched_collider: Collider,
+    /// How much shorter the crouched capsule is than the standing one.
+    height_difference: Scalar,
+    speed_multiplier: Scalar,
+}
+
+impl CrouchSettings {
+    /// Derives the crouched capsule from the standing one, keeping the radius.
+    pub fn new(
+        standing_collider: &Collider,
+        crouched_height: Scalar,
+        speed_multiplier: Scalar,
+    ) -> Self {
+        let aabb = standing_collider.aabb(Vector::ZERO, Quaternion::default());
+        let radius = (aabb.max.x - aabb.min.x) * 0.5;
+        let standing_height = aabb.max.y - aabb.min.y;
+        let crouched_height = crouched_height.clamp(radius * 2.0, standing_height);
+
+        Self {
+            standing_collider: standing_collider.clone(),
+            crouched_collider: Collider::capsule(radius, crouched_height - radius * 2.0),
+            height_difference: standing_height - crouched_height,
+            speed_multiplier,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct JumpImpulse(Scalar);
+
+/// Grace periods that make jumps more forgiving to time.
+#[derive(Component)]
+pub struct JumpForgiveness {
+    /// How long after leaving the ground the character can still jump.
+    coyote_time: Scalar,
+    /// How long a jump press is remembered before the character lands.
+    buffer_time: Scalar,
+}
+
+#[derive(Component, Default)]
+pub struct JumpState {
+    coyote_timer: Scalar,
+    buffer_timer: Scalar,
+    /// Whether the character is rising from a jump that can still be cut short.
+    is_jumping: bool,
+}
+
+/// How much upward velocity is kept when the jump key is released early.
+const JUMP_RELEASE_VELOCITY_FACTOR: Scalar = 0.5;
+
+#[derive(Component)]
+pub struct SprintMultiplier(Scalar);
+
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Sprinting;
+
+/// Drains while sprinting and regenerates after a short delay once the character stops.
+#[derive(Component)]
+pub struct Stamina {
+    pub current: Scalar,
+    pub max: Scalar,
+    drain_rate: Scalar,
+    regen_rate: Scalar,
+    regen_delay: Scalar,
+    regen_timer: Scalar,
+    /// Set when stamina runs out. The character can't sprint again until it recovers.
+    exhausted: bool,
+}
+
+impl Stamina {
+    pub const fn new(
+        max: Scalar,
+        drain_rate: Scalar,
+        regen_rate: Scalar,
+        regen_delay: Scalar,
+    ) -> Self {
+        Self {
+            current: max,
+            max,
+            drain_rate,
+            regen_rate,
+            regen_delay,
+            regen_timer: 0.0,
+            exhausted: false,
+        }
+    }
+}
+
+/// How much of their stamina an exhausted character has to regain before sprinting again.
+const STAMINA_RECOVERY_FRACTION: Scalar = 0.25;
+
+/// Horizontal speed below which a sprinting character counts as standing still.
+const SPRINT_MIN_SPEED: Scalar = 0.1;
+
+/// Sent when a character runs out of stamina and stops sprinting.
+#[derive(Message)]
+pub struct StaminaExhausted {
+    pub entity: Entity,
+}
+
+/// Sent when an exhausted character has regained enough stamina to sprint again.
+#[derive(Message)]
+pub struct StaminaRecovered {
+    pub entity: Entity,
+}
+
+/// Vertical offset of the character's cameras, eased towards the crouched or standing eye height.
+#[derive(Component, Default, Deref)]
+pub struct EyeOffset(Scalar);
+
+/// How quickly the cameras follow the eye height when crouching or standing up.
+const EYE_OFFSET_SPEED: Scalar = 12.0;
+
+#[derive(Bundle)]
+pub struct CharacterControllerBundle {
+    character_controller: CharacterController,
+    body: RigidBody,
+    interpolation: TranslationInterpolation,
+    collider: Collider,
+    collision_layers: CollisionLayers,
+    ground_caster: ShapeCaster,
+    gravity: ControllerGravity,
+    movement: MovementBundle,
+    jump: JumpBundle,
+    sprint: SprintBundle,
+    crouch: CrouchSettings,
+    eye_offset: EyeOffset,
+    slide_velocity: SlideVelocity,
+    ground_snap: GroundSnapDistance,
+    ground_info: GroundInfo,
+    mass: CharacterMass,
+    push_strength: PushStrength,
+    platform_motion: PlatformMotion,
+}
+
+#[derive(Bundle)]
+pub struct MovementBundle {
+    acceleration: MovementAcceleration,
+    damping: MovementDampingFactor,
+    max_slope_angle: MaxSlopeAngle,
+    max_step_height: MaxStepHeight,
+}
+impl MovementBundle {
+    pub const fn new(
+        accelarion: Scalar,
+        damping: Scalar,
+        max_slope_angle: Scalar,
+        max_step_height: Scalar,
+    ) -> Self {
+        Self {
+            acceleration: MovementAcceleration(accelarion),
+            damping: MovementDampingFactor(damping),
+            max_slope_angle: MaxSlopeAngle(max_slope_angle),
+            max_step_height: MaxStepHeight(max_step_height),
+        }
+    }
+}
+impl Default for MovementBundle {
+    fn default() -> Self {
+        Self::new(30.0, 0.9, PI * 0.45, 0.3)
+    }
+}
+
+#[derive(Bundle)]
+pub struct JumpBundle {
+    impulse: JumpImpulse,
+    forgiveness: JumpForgiveness,
+    state: JumpState,
+}
+impl JumpBundle {
+    pub fn new(impulse: Scalar, coyote_time: Scalar, buffer_time: Scalar) -> Self {
+        Self {
+            impulse: JumpImpulse(impulse),
+            forgiveness: JumpForgiveness {
+                coyote_time,
+                buffer_time,
+            },
+            state: JumpState::default(),
+        }
+    }
+}
+impl Default for JumpBundle {
+    fn default() -> Self {
+        Self::new(7.0, 0.15, 0.15)
+    }
+}
+
+#[derive(Bundle)]
+pub struct SprintBundle {
+    multiplier: SprintMultiplier,
+    stamina: Stamina,
+}
+impl SprintBundle {
+    pub const fn new(multiplier: Scalar, stamina: Stamina) -> Self {
+        Self {
+            multiplier: SprintMultiplier(multiplier),
+            stamina,
+        }
+    }
+}
+impl Default for SprintBundle {
+    fn default() -> Self {
+        Self::new(1.6, Stamina::new(100.0, 20.0, 15.0, 1.0))
+    }
+}
+
+impl CharacterControllerBundle {
+    pub fn new(collider: Collider, gravity: Vector) -> Self {
+        let crouch = CrouchSettings::new(&collider, 1.0, 0.5);
+        Self {
+            character_controller: CharacterController,
+            body: RigidBody::Kinematic,
+            interpolation: TranslationInterpolation,
+            ground_caster: ShapeCaster::new(
+                ground_caster_shape(&collider),
+                Vector::ZERO,
+                Quaternion::default(),
+                Dir3::NEG_Y,
+            )
+            .with_max_distance(0.2),
+            collider,
+            collision_layers: CollisionLayers::default(),
+            gravity: ControllerGravity(gravity),
+            movement: MovementBundle::default(),
+            jump: JumpBundle::default(),
+            sprint: SprintBundle::default(),
+            crouch,
+            eye_offset: EyeOffset::default(),
+            slide_velocity: SlideVelocity::default(),
+            ground_snap: GroundSnapDistance(0.3),
+            ground_info: GroundInfo::default(),
+            mass: CharacterMass(80.0),
+            push_strength: PushStrength(1.0),
+            platform_motion: PlatformMotion::default(),
+        }
+    }
+
+    pub fn with_movement(
+        mut self,
+        accelarion: Scalar,
+        damping: Scalar,
+        max_slope_angle: Scalar,
+        max_step_height: Scalar,
+    ) -> Self {
+        self.movement = MovementBundle::new(accelarion, damping, max_slope_angle, max_step_height);
+        self
+    }
+
+    /// Sets the layers the character is on and collides with. Ground checks and sweeps
+    /// only hit the layers it collides with.
+    pub fn with_collision_layers(mut self, collision_layers: CollisionLayers) -> Self {
+        self.ground_caster.query_filter = SpatialQueryFilter::from_mask(collision_layers.filters);
+        self.collision_layers = collision_layers;
+        self
+    }
+
+    pub fn with_ground_snap(mut self, distance: Scalar) -> Self {
+        self.ground_snap = GroundSnapDistance(distance);
+        self
+    }
+
+    pub fn with_push(mut self, mass: Scalar, push_strength: Scalar) -> Self {
+        self.mass = CharacterMass(mass);
+        self.push_strength = PushStrength(push_strength);
+        self
+    }
+
+    pub fn with_jump(mut self, impulse: Scalar, coyote_time: Scalar, buffer_time: Scalar) -> Self {
+        self.jump = JumpBundle::new(impulse, coyote_time, buffer_time);
+        self
+    }
+
+    pub fn with_sprint(mut self, multiplier: Scalar, stamina: Stamina) -> Self {
+        self.sprint = SprintBundle::new(multiplier, stamina);
+        self
+    }
+
+    pub fn with_crouch(mut self, crouched_height: Scalar, speed_multiplier: Scalar) -> Self {
+        self.crouch = CrouchSettings::new(&self.collider, crouched_height, speed_multiplier);
+        self
+    }
+}
+
+/// The ground caster is slightly smaller than the collider so it doesn't hit walls.
+fn ground_caster_shape(collider: &Collider) -> Collider {
+    let mut caster_shape = collider.clone();
+    caster_shape.set_scale(Vector::ONE * 0.99, 10);
+    caster_shape
+}
+
+fn movement_input(
+    mut movement_writer: MessageWriter<MovementMessage>,
+    action_state: Res<ActionState>,
+    mut was_jump_pressed: Local<bool>,
+    player: Single<(Entity, &Transform), With<Player>>,
+) {
+    let (entity, player) = player.into_inner();
+
+    // Several bindings can be held at once, e.g. two keys on a diagonal, so cap the total
+    // to full deflection.
+    let input = action_state.axis(AxisAction::Move).clamp_length_max(1.0);
+
+    let forward_dir = player.forward();
+    let right_dir = player.right();
+
+    let move_direction = (forward_dir * input.y) + (right_dir * input.x);
+
+    // Sent first so that the sprint state is up to date before moving.
+    let sprint = action_state.pressed(ButtonAction::Sprint);
+    movement_writer.write(MovementMessage::new(
+        entity,
+        MovementAction::IsSprinting(sprint),
+    ));
+
+    let direction = Vector2::new(move_direction.x, move_direction.z).normalize_or_zero();
+    movement_writer.write(MovementMessage::new(
+        entity,
+        MovementAction::Move {
+            direction,
+            magnitude: input.length(),
+        },
+    ));
+
+    // `just_pressed` is only set for a single frame, which a fixed tick can miss,
+    // so track the edges between ticks instead.
+    let jump = action_state.pressed(ButtonAction::Jump);
+    if jump && !*was_jump_pressed {
+        movement_writer.write(MovementMessage::new(entity, MovementAction::Jump));
+    }
+    if !jump && *was_jump_pressed {
+        movement_writer.write(MovementMessage::new(entity, MovementAction::JumpReleased));
+    }
+    *was_jump_pressed = jump;
+
+    // Sent every frame so that a refused stand-up is retried once there is headroom.
+    let crouch = action_state.pressed(ButtonAction::Crouch);
+    movement_writer.write(MovementMessage::new(
+        entity,
+        MovementAction::IsCrouching(crouch),
+    ));
+}
+
+fn look_input(
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    cursor_state: Res<CursorState>,
+    mut smoothed_rate: Local<Vec2>,
+    player: Single<
+        (
+            &mut Transform,
+            &CameraSensitivity,
+            Option<&StickSensitivity>,
+            &CameraRig,
+        ),
+        With<Player>,
+    >,
+    mut camera: Query<&mut Transform, (With<Camera3d>, Without<Player>)>,
+) {
+    let (mut transform, camera_sensitivity, stick_sensitivity, rig) = player.into_inner();
+    let delta_time = time.delta_secs();
+
+    // The mouse is free for UI while the cursor is released.
+    let mut look = if cursor_state.is_grabbed() {
+        action_state.axis(AxisAction::Look)
+    } else {
+        Vec2::ZERO
+    };
+    if rig.acceleration > 0.0 && delta_time > 0.0 {
+        let speed = look.length() / delta_time;
+        look *= 1.0 + rig.acceleration * speed / 1000.0;
+    }
+
+    // Mouse motion is already a distance, stick deflection is a turn rate.
+    let mut delta = look * camera_sensitivity.0;
+    if let Some(stick_sensitivity) = stick_sensitivity {
+        delta += action_state.axis(AxisAction::Turn) * stick_sensitivity.0 * delta_time;
+    }
+
+    // Smooth the turn rate rather than the per-frame delta so it behaves the same at any
+    // frame rate.
+    if rig.smoothing > 0.0 && delta_time > 0.0 {
+        let blend = 1.0 - (-delta_time / rig.smoothing).exp();
+        *smoothed_rate = smoothed_rate.lerp(delta / delta_time, blend);
+        delta = *smoothed_rate * delta_time;
+    } else {
+        *smoothed_rate = Vec2::ZERO;
+    }
+
+    if rig.invert_y {
+        delta.y = -delta.y;
+    }
+
+    if delta != Vec2::ZERO {
+        let delta_yaw = -delta.x;
+        let delta_pitch = delta.y;
+
+        let (player_yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        let player_new_yaw = player_yaw + delta_yaw;
+
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, player_new_yaw, 0.0, 0.0);
+
+        for mut camera_transform in camera.iter_mut() {
+            let (_, camera_pitch, _) = camera_transform.rotation.to_euler(EulerRot::YXZ);
+            let camera_new_pitch = (camera_pitch + delta_pitch).clamp(rig.min_pitch, rig.max_pitch);
+            camera_transform.rotation = Quat::from_euler(EulerRot::YXZ, 0.0, camera_new_pitch, 0.0);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_grounded(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ShapeHits,
+            &Rotation,
+            Option<&MaxSlopeAngle>,
+            &mut GroundInfo,
+        ),
+        With<CharacterController>,
+    >,
+) {
+    for (entity, hits, rotation, max_slope_angle, mut ground_info) in &mut query {
+        // The character is grounded if the shape caster has a hit with a normal
+        // that isn't too steep. If there are several, the flattest one is the ground.
+        let ground = hits
+            .iter()
+            .map(|hit| (hit.entity, rotation * -hit.normal2))
+            .filter(|(_, normal)| {
+                max_slope_angle.is_none_or(|angle| normal.angle_between(Vector::Y).abs() <= angle.0)
+            })
+            .max_by(|(_, a), (_, b)| a.y.total_cmp(&b.y));
+
+        if let Some((ground_entity, normal)) = ground {
+            *ground_info = GroundInfo {
+                normal,
+                slope_angle: normal.angle_between(Vector::Y),
+                entity: Some(ground_entity),
+            };
+            commands.entity(entity).insert(Grounded);
+        } else {
+            *ground_info = GroundInfo::default();
+            commands.entity(entity).remove::<Grounded>();
+        }
+    }
+}
+
+/// Responds to [`MovementMessage`]s and moves the addressed character controllers accordingly.
+#[allow(clippy::type_complexity)]
+fn movement(
+    mut commands: Commands,
+    time: Res<Time>,
+    spatial_query: SpatialQuery,
+    mut movement_reader: MessageReader<MovementMessage>,
+    mut controllers: Query<(
+        Entity,
+        (&MovementAcceleration, &MovementDampingFactor),
+        &CrouchSettings,
+        &mut LinearVelocity,
+        &mut Position,
+        &Rotation,
+        &mut Collider,
+        &mut ShapeCaster,
+        &mut EyeOffset,
+        (&JumpForgiveness, &mut JumpState),
+        &GroundInfo,
+        &CharacterMass,
+        (&SprintMultiplier, &Stamina, Has<Sprinting>),
+        Has<Grounded>,
+        Has<Crouched>,
+    )>,
+) {
+    // Precision is adjusted so that the example works with
+    // both the `f32` and `f64` features. Otherwise you don't need this.
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for message in movement_reader.read() {
+        let Ok((
+            entity,
+            (movement_acceleration, damping_factor),
+            crouch,
+            mut linear_velocity,
+            mut position,
+            rotation,
+            mut collider,
+            mut ground_caster,
+            mut eye_offset,
+            (jump_forgiveness, mut jump_state),
+            ground_info,
+            mass,
+            (sprint_multiplier, stamina, is_sprinting),
+            is_grounded,
+            is_crouched,
+        )) = controllers.get_mut(message.entity)
+        else {
+            continue;
+        };
+
+        match message.action {
+            MovementAction::Move {
+                direction,
+                magnitude,
+            } => {
+                let mut acceleration = movement_acceleration.0 * magnitude.clamp(0.0, 1.0);
+                if is_crouched {
+                    acceleration *= crouch.speed_multiplier;
+                } else if is_sprinting {
+                    acceleration *= sprint_multiplier.0;
+                }
+                let mut direction = Vector::new(direction.x, 0.0, direction.y).normalize_or_zero();
+
+                // Project the input onto the ground so that the character covers the
+                // same distance along a slope as it would on flat ground.
+                if is_grounded {
+                    let along_slope = direction
+                        .reject_from_normalized(ground_info.normal)
+                        .normalize_or_zero();
+                    direction = Vector::new(along_slope.x, 0.0, along_slope.z);
+
+                    // Climbing is slower, the more so the steeper the slope.
+                    if along_slope.y > 0.0 {
+                        acceleration *= ground_info.slope_angle.cos();
+                    }
+                }
+
+                let step = damped_step(damping_factor.0, delta_time);
+                linear_velocity.x += direction.x * acceleration * step;
+                linear_velocity.z += direction.z * acceleration * step;
+            }
+            MovementAction::Jump => {
+                // The jump itself happens in `apply_jump` once the character can jump.
+                jump_state.buffer_timer = jump_forgiveness.buffer_time;
+            }
+            MovementAction::JumpReleased => {
+                if jump_state.is_jumping && linear_velocity.y > 0.0 {
+                    linear_velocity.y *= JUMP_RELEASE_VELOCITY_FACTOR;
+                }
+                jump_state.is_jumping = false;
+            }
+            MovementAction::Knockback(impulse) => {
+                linear_velocity.0 += mass.velocity_change(impulse);
+            }
+            MovementAction::IsSprinting(true) => {
+                if !is_sprinting && !is_crouched && !stamina.exhausted && stamina.current > 0.0 {
+                    commands.entity(entity).insert(Sprinting);
+                }
+            }
+            MovementAction::IsSprinting(false) => {
+                if is_sprinting {
+                    commands.entity(entity).remove::<Sprinting>();
+                }
+            }
+            MovementAction::IsCrouching(true) => {
+                if is_grounded && !is_crouched {
+                    // Lower the center so the feet stay on the ground, and keep the
+                    // cameras where they were so they can ease down instead of snapping.
+                    let half_difference = crouch.height_difference * 0.5;
+                    position.y -= half_difference;
+                    eye_offset.0 += half_difference;
+                    *collider = crouch.crouched_collider.clone();
+                    ground_caster.shape = ground_caster_shape(&crouch.crouched_collider);
+                    // Crouching cancels a sprint, which can't start again until standing up.
+                    commands
+                        .entity(entity)
+                        .insert(Crouched)
+                        .remove::<Sprinting>();
+                }
+            }
+            MovementAction::IsCrouching(false) => {
+                if is_crouched
+                    && has_headroom(
+                        &spatial_query,
+                        &ground_caster.query_filter,
+                        entity,
+                        crouch,
+                        position.0,
+                        rotation,
+                    )
+                {
+                    let half_difference = crouch.height_difference * 0.5;
+                    position.y += half_difference;
+                    eye_offset.0 -= half_difference;
+                    *collider = crouch.standing_collider.clone();
+                    ground_caster.shape = ground_caster_shape(&crouch.standing_collider);
+                    commands.entity(entity).remove::<Crouched>();
+                }
+            }
+        }
+    }
+}
+
+/// Casts the crouched capsule upwards to check whether the standing capsule would fit.
+fn has_headroom(
+    spatial_query: &SpatialQuery,
+    filter: &SpatialQueryFilter,
+    entity: Entity,
+    crouch: &CrouchSettings,
+    position: Vector,
+    rotation: &Rotation,
+) -> bool {
+    spatial_query
+        .cast_shape(
+            &crouch.crouched_collider,
+            position,
+            rotation.0,
+            Dir3::Y,
+            &ShapeCastConfig::from_max_distance(crouch.height_difference),
+            &filter.clone().with_excluded_entities([entity]),
+        )
+        .is_none()
+}
+
+/// Eases the eye offset towards the standing or crouched eye height and applies it to child cameras.
+fn update_eye_offset(
+    time: Res<Time>,
+    mut controllers: Query<(&mut EyeOffset, &CrouchSettings, &Children, Has<Crouched>)>,
+    mut cameras: Query<&mut Transform, With<Camera3d>>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for (mut eye_offset, crouch, children, is_crouched) in &mut controllers {
+        let target = if is_crouched {
+            -crouch.height_difference * 0.5
+        } else {
+            0.0
+        };
+        eye_offset.0 = eye_offset
+            .0
+            .lerp(target, 1.0 - (-EYE_OFFSET_SPEED * delta_time).exp());
+
+        let mut cameras = cameras.iter_many_mut(children);
+        while let Some(mut camera_transform) = cameras.fetch_next() {
+            camera_transform.translation.y = eye_offset.0;
+        }
+    }
+}
+
+/// Performs buffered jumps while the character is grounded or within coyote time.
+fn apply_jump(
+    time: Res<Time>,
+    mut controllers: Query<(
+        &JumpImpulse,
+        &JumpForgiveness,
+        &mut JumpState,
+        &mut LinearVelocity,
+        Has<Grounded>,
+    )>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for (jump_impulse, jump_forgiveness, mut jump_state, mut linear_velocity, is_grounded) in
+        &mut controllers
+    {
+        // The ground caster can still hit the ground right after a jump,
+        // so only refresh coyote time once the character stops rising.
+        if is_grounded && linear_velocity.y <= 0.0 {
+            jump_state.coyote_timer = jump_forgiveness.coyote_time;
+            jump_state.is_jumping = false;
+        } else {
+            jump_state.coyote_timer -= delta_time;
+        }
+
+        if jump_state.buffer_timer > 0.0 && jump_state.coyote_timer > 0.0 {
+            linear_velocity.y = jump_impulse.0;
+            jump_state.buffer_timer = 0.0;
+            jump_state.coyote_timer = 0.0;
+            jump_state.is_jumping = true;
+        } else {
+            jump_state.buffer_timer -= delta_time;
+        }
+    }
+}
+
+/// Drains stamina while sprinting and regenerates it after a delay otherwise.
+fn update_stamina(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut exhausted_writer: MessageWriter<StaminaExhausted>,
+    mut recovered_writer: MessageWriter<StaminaRecovered>,
+    mut controllers: Query<(Entity, &mut Stamina, &LinearVelocity, Has<Sprinting>)>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for (entity, mut stamina, linear_velocity, is_sprinting) in &mut controllers {
+        let horizontal_speed = Vector2::new(linear_velocity.x, linear_velocity.z).length();
+
+        if is_sprinting && horizontal_speed > SPRINT_MIN_SPEED {
+            stamina.current = (stamina.current - stamina.drain_rate * delta_time).max(0.0);
+            stamina.regen_timer = 0.0;
+
+            if stamina.current <= 0.0 {
+                stamina.exhausted = true;
+                commands.entity(entity).remove::<Sprinting>();
+                exhausted_writer.write(StaminaExhausted { entity });
+            }
+            continue;
+        }
+
+        stamina.regen_timer += delta_time;
+        if stamina.regen_timer < stamina.regen_delay {
+            continue;
+        }
+
+        stamina.current = (stamina.current + stamina.regen_rate * delta_time).min(stamina.max);
+        if stamina.exhausted && stamina.current >= stamina.max * STAMINA_RECOVERY_FRACTION {
+            stamina.exhausted = false;
+            recovered_writer.write(StaminaRecovered { entity });
+        }
+    }
+}
+
+/// How long an acceleration has to act for over a step of `delta_time`, so that together with
+/// the damping applied after it, speed follows the same curve at any timestep.
+fn damped_step(damping_factor: Scalar, delta_time: Scalar) -> Scalar {
+    let decay_rate = -damping_factor.ln() * DAMPING_REFERENCE_RATE;
+    let kept = (-decay_rate * delta_time).exp();
+    if decay_rate <= Scalar::EPSILON || kept <= 0.0 {
+        return delta_time;
+    }
+
+    // Solves `dv/dt = a - decay_rate * v` exactly over the step, before the damping is applied.
+    (1.0 - kept) / (decay_rate * kept)
+}
+
+fn apply_movement_damping(
+    time: Res<Time>,
+    mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for (damping_factor, mut linear_velocity) in &mut query {
+        // We could use `LinearDamping`, but we don't want to dampen movement along the Y axis
+        let damping = damping_factor.0.powf(delta_time * DAMPING_REFERENCE_RATE);
+        linear_velocity.x *= damping;
+        linear_velocity.z *= damping;
+    }
+}
+
+fn apply_gravity(
+    time: Res<Time>,
+    mut controllers: Query<(&ControllerGravity, &mut LinearVelocity)>,
+) {
+    // Precision is adjusted so that the example works with
+    // both the `f32` and `f64` features. Otherwise you don't need this.
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for (gravity, mut linear_velocity) in &mut controllers {
+        linear_velocity.0 += gravity.0 * delta_time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use avian3d::prelude::PhysicsPlugins;
+    use bevy::{input::InputPlugin, time::TimeUpdateStrategy};
+
+    use super::*;
+    use crate::plugins::actions::{ActionMap, ActionsPlugin};
+
+    /// A headless app with physics and character controllers that runs one fixed tick per update.
+    pub(super) fn app() -> App {
+        app_at(64.0)
+    }
+
+    /// Like [`app`], with the fixed timestep running at `hz`.
+    fn app_at(hz: f64) -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            TransformPlugin,
+            PhysicsPlugins::default(),
+            CharacterControllerPlugin,
+        ))
+        .init_asset::<Mesh>()
+        .init_resource::<ActionState>()
+        .init_resource::<CursorState>()
+        .insert_resource(Time::<Fixed>::from_hz(hz))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / hz,
+        )))
+        .add_systems(FixedUpdate, send_intents.before(CharacterControllerSystems));
+        app
+    }
+
+    /// What a test controller is trying to do, sent every tick the way player input is.
+    #[derive(Component, Default)]
+    pub(super) struct Intent {
+        pub direction: Vector2,
+        pub crouch: bool,
+        pub sprint: bool,
+    }
+
+    fn send_intents(
+        mut movement_writer: MessageWriter<MovementMessage>,
+        intents: Query<(Entity, &Intent)>,
+    ) {
+        for (entity, intent) in &intents {
+            movement_writer.write(MovementMessage::new(
+                entity,
+                MovementAction::IsSprinting(intent.sprint),
+            ));
+            movement_writer.write(MovementMessage::new(
+                entity,
+                MovementAction::Move {
+                    direction: intent.direction.normalize_or_zero(),
+                    magnitude: intent.direction.length().min(1.0),
+                },
+            ));
+            movement_writer.write(MovementMessage::new(
+                entity,
+                MovementAction::IsCrouching(intent.crouch),
+            ));
+        }
+    }
+
+    /// A player sized controller with its feet just above `translation`.
+    pub(super) fn spawn_controller(app: &mut App, translation: Vector, gravity: Vector) -> Entity {
+        app.world_mut()
+            .spawn((
+                Transform::from_translation(translation + Vector::Y * 0.95),
+                CharacterControllerBundle::new(Collider::capsule(0.4, 1.0), gravity),
+                Intent::default(),
+            ))
+            .id()
+    }
+
+    pub(super) fn spawn_box(
+        app: &mut App,
+        body: RigidBody,
+        center: Vector,
+        size: Vector,
+    ) -> Entity {
+        app.world_mut()
+            .spawn((
+                body,
+                Collider::cuboid(size.x, size.y, size.z),
+                Transform::from_translation(center),
+            ))
+            .id()
+    }
+
+    /// Steps the app until `seconds` more of fixed time have passed.
+    pub(super) fn run(app: &mut App, seconds: f64) {
+        let time = app.world().resource::<Time<Fixed>>();
+        let ticks = (seconds / time.timestep().as_secs_f64()).round() as u32;
+        let end = time.elapsed() + time.timestep() * ticks;
+        while app.world().resource::<Time<Fixed>>().elapsed() < end {
+            app.update();
+        }
+    }
+
+    pub(super) fn position(app: &App, entity: Entity) -> Vector {
+        app.world().get::<Position>(entity).unwrap().0
+    }
+
+    pub(super) fn intent(app: &mut App, entity: Entity) -> Mut<'_, Intent> {
+        app.world_mut().get_mut::<Intent>(entity).unwrap()
+    }
+
+    /// Sends a one-off action, like a jump, that [`Intent`] doesn't cover.
+    fn send(app: &mut App, entity: Entity, action: MovementAction) {
+        app.world_mut()
+            .write_message(MovementMessage::new(entity, action));
+    }
+
+    fn vertical_velocity(app: &App, entity: Entity) -> Scalar {
+        app.world().get::<LinearVelocity>(entity).unwrap().y
+    }
+
+    fn height(app: &App, entity: Entity) -> Scalar {
+        let aabb = app
+            .world()
+            .get::<Collider>(entity)
+            .unwrap()
+            .aabb(Vector::ZERO, Quaternion::default());
+        aabb.max.y - aabb.min.y
+    }
+
+    fn spawn_floor(app: &mut App) -> Entity {
+        spawn_box(
+            app,
+            RigidBody::Static,
+            Vector::NEG_Y * 0.5,
+            Vector::new(20.0, 1.0, 20.0),
+        )
+    }
+
+    /// Spawns a controller on the floor and crouches it. Returns its height while standing.
+    fn crouch_on_floor(app: &mut App) -> (Entity, Scalar) {
+        spawn_floor(app);
+        let character = spawn_controller(app, Vector::ZERO, Vector::NEG_Y * 9.81);
+        run(app, 0.5);
+        assert!(app.world().entity(character).contains::<Grounded>());
+        let standing_y = position(app, character).y;
+
+        intent(app, character).crouch = true;
+        run(app, 0.5);
+        (character, standing_y)
+    }
+
+    #[test]
+    fn only_the_addressed_controller_moves() {
+        let mut app = app();
+        let addressed = spawn_controller(&mut app, Vector::ZERO, Vector::ZERO);
+        let other = spawn_controller(&mut app, Vector::X * 5.0, Vector::ZERO);
+        app.world_mut().entity_mut(other).remove::<Intent>();
+        intent(&mut app, addressed).direction = Vector2::Y;
+        run(&mut app, 0.1);
+        let other_start = position(&app, other);
+
+        run(&mut app, 1.0);
+        assert!(position(&app, addressed).z > 1.0);
+        assert!(position(&app, other).distance(other_start) < 1e-4);
+    }
+
+    #[test]
+    fn distance_covered_does_not_depend_on_the_timestep() {
+        let distance_at = |hz| {
+            let mut app = app_at(hz);
+            let character = spawn_controller(&mut app, Vector::ZERO, Vector::ZERO);
+            intent(&mut app, character).direction = Vector2::X;
+            run(&mut app, 2.0);
+            position(&app, character).x
+        };
+
+        let (slow, fast) = (distance_at(30.0), distance_at(240.0));
+        assert!(slow > 1.0, "{slow}");
+        assert!(
+            (slow - fast).abs() < fast * 0.02,
+            "{slow} m at 30 Hz, {fast} m at 240 Hz"
+        );
+    }
+
+    #[test]
+    fn crouching_shrinks_the_collider_and_keeps_the_feet_on_the_ground() {
+        let mut app = app();
+        let (character, standing_y) = crouch_on_floor(&mut app);
+
+        assert!(app.world().entity(character).contains::<Crouched>());
+        assert!((height(&app, character) - 1.0).abs() < 1e-3);
+        let lowered = standing_y - position(&app, character).y;
+        assert!((lowered - 0.4).abs() < 0.02, "lowered by {lowered}");
+        assert!(app.world().entity(character).contains::<Grounded>());
+    }
+
+    #[test]
+    fn stays_crouched_under_a_low_ceiling_until_there_is_headroom() {
+        let mut app = app();
+        let (character, standing_y) = crouch_on_floor(&mut app);
+
+        // Low enough that the standing capsule wouldn't fit, but clear of the crouched one.
+        let ceiling = spawn_box(
+            &mut app,
+            RigidBody::Static,
+            Vector::Y * 1.5,
+            Vector::new(4.0, 0.6, 4.0),
+        );
+        run(&mut app, 0.1);
+
+        intent(&mut app, character).crouch = false;
+        run(&mut app, 0.5);
+        assert!(app.world().entity(character).contains::<Crouched>());
+        assert!((height(&app, character) - 1.0).abs() < 1e-3);
+
+        app.world_mut().despawn(ceiling);
+        run(&mut app, 0.5);
+        assert!(!app.world().entity(character).contains::<Crouched>());
+        assert!((height(&app, character) - 1.8).abs() < 1e-3);
+        assert!((position(&app, character).y - standing_y).abs() < 0.02);
+    }
+
+    #[test]
+    fn crouching_stops_sprinting_and_draining_stamina() {
+        let mut app = app();
+        spawn_floor(&mut app);
+        let character = spawn_controller(&mut app, Vector::ZERO, Vector::NEG_Y * 9.81);
+        let mut held = intent(&mut app, character);
+        held.direction = Vector2::X;
+        held.sprint = true;
+        run(&mut app, 0.5);
+        assert!(app.world().entity(character).contains::<Sprinting>());
+
+        intent(&mut app, character).crouch = true;
+        run(&mut app, 0.1);
+        assert!(!app.world().entity(character).contains::<Sprinting>());
+
+        let stamina = |app: &App| app.world().get::<Stamina>(character).unwrap().current;
+        let crouched_stamina = stamina(&app);
+        run(&mut app, 1.0);
+        assert!(app.world().entity(character).contains::<Crouched>());
+        assert!(!app.world().entity(character).contains::<Sprinting>());
+        assert!(stamina(&app) >= crouched_stamina);
+    }
+
+    /// Walks a character off the end of the floor at `x = 0` and jumps `delay` seconds
+    /// after it leaves the ground. Returns its vertical velocity right after.
+    fn jump_after_walking_off_a_ledge(delay: f64) -> Scalar {
+        let mut app = app();
+        spawn_box(
+            &mut app,
+            RigidBody::Static,
+            Vector::new(-5.0, -0.5, 0.0),
+            Vector::new(10.0, 1.0, 4.0),
+        );
+        let character = spawn_controller(&mut app, Vector::NEG_X * 2.0, Vector::NEG_Y * 9.81);
+        run(&mut app, 0.5);
+        assert!(app.world().entity(character).contains::<Grounded>());
+
+        intent(&mut app, character).direction = Vector2::X;
+        for _ in 0..1000 {
+            if !app.world().entity(character).contains::<Grounded>() {
+                break;
+            }
+            app.update();
+        }
+        assert!(!app.world().entity(character).contains::<Grounded>());
+
+        run(&mut app, delay);
+        send(&mut app, character, MovementAction::Jump);
+        app.update();
+        vertical_velocity(&app, character)
+    }
+
+    #[test]
+    fn can_still_jump_just_after_walking_off_a_ledge() {
+        let coyote_jump = jump_after_walking_off_a_ledge(0.05);
+        assert!(coyote_jump > 5.0, "{coyote_jump}");
+
+        let late_jump = jump_after_walking_off_a_ledge(0.3);
+        assert!(late_jump <= 0.0, "{late_jump}");
+    }
+
+    /// Drops a character onto the floor from 2 m, pressing jump once its feet are within
+    /// `press_height` of the floor. Returns the fastest it rises over the next second.
+    fn rise_after_pressing_jump_at(press_height: Scalar) -> Scalar {
+        let mut app = app();
+        spawn_floor(&mut app);
+        let character = spawn_controller(&mut app, Vector::Y * 2.0, Vector::NEG_Y * 9.81);
+        // The feet are 0.9 m below the center of the standing capsule.
+        for _ in 0..1000 {
+            if position(&app, character).y - 0.9 <= press_height {
+                break;
+            }
+            app.update();
+        }
+
+        send(&mut app, character, MovementAction::Jump);
+        let mut rise: Scalar = 0.0;
+        for _ in 0..64 {
+            app.update();
+            rise = rise.max(vertical_velocity(&app, character));
+        }
+        rise
+    }
+
+    #[test]
+    fn jumps_pressed_just_before_landing_are_buffered() {
+        let buffered = rise_after_pressing_jump_at(0.3);
+        assert!(buffered > 5.0, "{buffered}");
+
+        // Pressed as soon as it starts falling, too long before landing to be remembered.
+        let forgotten = rise_after_pressing_jump_at(10.0);
+        assert!(forgotten < 1.0, "{forgotten}");
+    }
+
+    /// Jumps from the floor, letting go after `release_after` seconds if given. Returns the
+    /// height of the peak above the start.
+    fn jump_height(release_after: Option<f64>) -> Scalar {
+        let mut app = app();
+        spawn_floor(&mut app);
+        let character = spawn_controller(&mut app, Vector::ZERO, Vector::NEG_Y * 9.81);
+        run(&mut app, 0.5);
+        let start = position(&app, character).y;
+
+        send(&mut app, character, MovementAction::Jump);
+        if let Some(hold) = release_after {
+            run(&mut app, hold);
+            send(&mut app, character, MovementAction::JumpReleased);
+        }
+        let mut peak = position(&app, character).y;
+        for _ in 0..128 {
+            app.update();
+            peak = peak.max(position(&app, character).y);
+        }
+        peak - start
+    }
+
+    #[test]
+    fn releasing_jump_early_lowers_the_peak() {
+        let full = jump_height(None);
+        let short = jump_height(Some(0.1));
+        assert!(full > 2.0, "{full}");
+        assert!(short > 0.2, "{short}");
+        assert!(
+            short < full * 0.6,
+            "{short} m when released early, {full} m when held"
+        );
+    }
+
+    #[test]
+    fn knockback_moves_heavier_characters_less() {
+        let mut app = app();
+        let light = spawn_controller(&mut app, Vector::ZERO, Vector::ZERO);
+        let heavy = spawn_controller(&mut app, Vector::X * 5.0, Vector::ZERO);
+        app.world_mut()
+            .entity_mut(heavy)
+            .insert(CharacterMass(160.0));
+        run(&mut app, 0.1);
+
+        for entity in [light, heavy] {
+            app.world_mut().write_message(MovementMessage::new(
+                entity,
+                MovementAction::Knockback(Vector::Z * 160.0),
+            ));
+        }
+        run(&mut app, 0.5);
+        let (light_distance, heavy_distance) = (position(&app, light).z, position(&app, heavy).z);
+        assert!(light_distance > 0.1, "{light_distance}");
+        assert!(
+            (light_distance - heavy_distance * 2.0).abs() < light_distance * 0.01,
+            "{light_distance} m at 80 kg, {heavy_distance} m at 160 kg"
+        );
+    }
+
+    #[test]
+    fn top_speed_is_the_same_in_all_eight_directions() {
+        use KeyCode::{KeyA, KeyD, KeyS, KeyW};
+
+        // Driven through the real bindings, so a diagonal is two keys held at once.
+        let velocity_holding = |keys: &[KeyCode]| {
+            let mut app = app();
+            app.add_plugins((InputPlugin, ActionsPlugin))
+                .insert_resource(ActionMap::default());
+            let character = spawn_controller(&mut app, Vector::ZERO, Vector::ZERO);
+            app.world_mut()
+                .entity_mut(character)
+                .remove::<Intent>()
+                .insert(Player);
+            let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            for &key in keys {
+                keyboard_input.press(key);
+            }
+            run(&mut app, 2.0);
+            let velocity = app.world().get::<LinearVelocity>(character).unwrap().0;
+            Vector2::new(velocity.x, velocity.z)
+        };
+
+        // Forward is -Z and right is +X.
+        let directions: [(&[KeyCode], Vector2); 8] = [
+            (&[KeyW], Vector2::new(0.0, -1.0)),
+            (&[KeyW, KeyD], Vector2::new(1.0, -1.0)),
+            (&[KeyD], Vector2::new(1.0, 0.0)),
+            (&[KeyS, KeyD], Vector2::new(1.0, 1.0)),
+            (&[KeyS], Vector2::new(0.0, 1.0)),
+            (&[KeyS, KeyA], Vector2::new(-1.0, 1.0)),
+            (&[KeyA], Vector2::new(-1.0, 0.0)),
+            (&[KeyW, KeyA], Vector2::new(-1.0, -1.0)),
+        ];
+
+        let top_speed = velocity_holding(&[KeyW]).length();
+        assert!(top_speed > 1.0, "{top_speed}");
+        for (keys, expected) in directions {
+            let velocity = velocity_holding(keys);
+            assert!(
+                (velocity.length() - top_speed).abs() < top_speed * 0.01,
+                "{keys:?}: {velocity} instead of {top_speed} m/s"
+            );
+            assert!(
+                velocity.normalize().dot(expected.normalize()) > 0.999,
+                "{keys:?}: {velocity}"
+            );
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use avian3d::{
+    math::*,
+    prelude::{
+        AngularVelocity, ColliderOf, ComputedCenterOfMass, LinearVelocity, Position, Rotation,
+    },
+};
+use bevy::prelude::*;
+
+use super::{CharacterController, GroundInfo, Grounded};
+
+/// How the ground under a character is moving, so that it can be carried along with it.
+#[derive(Component, Default)]
+pub struct PlatformMotion {
+    /// The collider the character is standing on.
+    pub ground: Option<Entity>,
+    /// The platform's velocity at the character's position.
+    pub linear: Vector,
+}
+
+/// Samples the velocity of whatever each grounded character is standing on.
+///
+/// The character's own velocity is relative to its platform. When it steps onto or off
+/// a platform, the difference is folded into its own velocity, so jumping off a moving
+/// platform keeps its momentum and landing on one doesn't fling the character.
+#[allow(clippy::type_complexity)]
+pub(super) fn track_platforms(
+    time: Res<Time>,
+    mut controllers: Query<
+        (
+            &GroundInfo,
+            &Position,
+            &mut Transform,
+            &mut LinearVelocity,
+            &mut PlatformMotion,
+            Has<Grounded>,
+        ),
+        With<CharacterController>,
+    >,
+    collider_rbs: Query<&ColliderOf>,
+    platforms: Query<
+        (
+            &Position,
+            &Rotation,
+            &LinearVelocity,
+            &AngularVelocity,
+            Option<&ComputedCenterOfMass>,
+        ),
+        Without<CharacterController>,
+    >,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+
+    for (
+        ground_info,
+        position,
+        mut transform,
+        mut linear_velocity,
+        mut platform_motion,
+        is_grounded,
+    ) in &mut controllers
+    {
+        let ground = ground_info.entity.filter(|_| is_grounded);
+        let platform = ground
+            .and_then(|collider| collider_rbs.get(collider).ok())
+            .and_then(|&ColliderOf { body }| platforms.get(body).ok());
+
+        let (linear, angular) = match platform {
+            Some((
+                platform_position,
+                platform_rotation,
+                platform_linear,
+                platform_angular,
+                center_of_mass,
+            )) => {
+                let center = platform_position.0
+                    + platform_rotation * center_of_mass.map_or(Vector::ZERO, |com| com.0);
+                let offset = position.0 - center;
+                (
+                    platform_linear.0 + platform_angular.0.cross(offset),
+                    platform_angular.0,
+                )
+            }
+            None => (Vector::ZERO, Vector::ZERO),
+        };
+
+        if ground != platform_motion.ground {
+            linear_velocity.0 += platform_motion.linear - linear;
+        }
+
+        // Turn with rotating platforms. Only yaw is inherited so the character stays upright.
+        if angular.y != 0.0 {
+            transform.rotate_y(angular.y * delta_time);
+        }
+
+        *platform_motion = PlatformMotion { ground, linear };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use avian3d::prelude::RigidBody;
+
+    use super::super::tests::{app, position, run, spawn_box, spawn_controller};
+    use super::*;
+
+    /// A large kinematic platform with its top at the origin, and a character standing on it.
+    fn platform_with_character(
+        app: &mut App,
+        velocity: impl Bundle,
+        character_translation: Vector,
+    ) -> Entity {
+        let platform = spawn_box(
+            app,
+            RigidBody::Kinematic,
+            Vector::NEG_Y * 0.25,
+            Vector::new(20.0, 0.5, 20.0),
+        );
+        app.world_mut().entity_mut(platform).insert(velocity);
+        let character = spawn_controller(app, character_translation, Vector::NEG_Y * 9.81);
+
+        // Land and pick up the platform's speed.
+        run(app, 1.0);
+        character
+    }
+
+    #[test]
+    fn carried_along_by_moving_platforms() {
+        let mut app = app();
+        let character =
+            platform_with_character(&mut app, LinearVelocity(Vector::X * 2.0), Vector::ZERO);
+
+        let start = position(&app, character);
+        run(&mut app, 1.0);
+        let moved = position(&app, character) - start;
+        assert!((moved.x - 2.0).abs() < 0.1, "{moved}");
+        assert!(moved.y.abs() < 0.05 && moved.z.abs() < 0.05, "{moved}");
+    }
+
+    #[test]
+    fn carried_around_and_turned_by_rotating_platforms() {
+        let mut app = app();
+        let character =
+            platform_with_character(&mut app, AngularVelocity(Vector::Y * 1.0), Vector::X * 2.0);
+
+        // Counterclockwise seen from above, which is the direction positive yaw turns in.
+        let angle = |position: Vector| (-position.z).atan2(position.x);
+        let yaw = |app: &App| {
+            let rotation = app.world().get::<Transform>(character).unwrap().rotation;
+            rotation.to_euler(EulerRot::YXZ).0
+        };
+        let (start, start_yaw) = (position(&app, character), yaw(&app));
+        run(&mut app, 1.0);
+        let end = position(&app, character);
+
+        assert!(
+            (angle(end) - angle(start) - 1.0).abs() < 0.1,
+            "{start} -> {end}"
+        );
+        assert!((end.xz().length() - 2.0).abs() < 0.1, "{end}");
+        assert!((yaw(&app) - start_yaw - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,30 @@
+use avian3d::prelude::{CollisionLayers, LayerMask, PhysicsLayer};
+
+/// What a collider is, which decides what it collides with and what queries see it.
+#[derive(PhysicsLayer, Clone, Copy, Debug, Default)]
+pub enum GameLayer {
+    /// Level geometry and props. Colliders without [`CollisionLayers`] are on this layer.
+    #[default]
+    World,
+    Player,
+    Enemy,
+    Projectile,
+}
+
+impl GameLayer {
+    /// Level geometry blocks everything.
+    pub fn world() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::World, LayerMask::ALL)
+    }
+
+    pub fn player() -> CollisionLayers {
+        CollisionLayers::new(
+            GameLayer::Player,
+            [GameLayer::World, GameLayer::Enemy, GameLayer::Projectile],
+        )
+    }
+
+    pub fn enemy() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Enemy, LayerMask::ALL)
+    }
+}
@@ -0,0 +1,18 @@
+use bevy::prelude::*;
+
+/// Hit points.
+#[derive(Component, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub const fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+}
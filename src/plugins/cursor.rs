@@ -0,0 +1,91 @@
+use bevy::{
+    input::InputSystems,
+    prelude::*,
+    window::{CursorGrabMode, CursorOptions, PrimaryWindow, WindowFocused},
+};
+use bevy_inspector_egui::bevy_egui::input::EguiWantsInput;
+
+use crate::plugins::actions::ActionSystems;
+
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorState>()
+            // Before actions are read, so the click that grabs the cursor can be swallowed.
+            .add_systems(
+                PreUpdate,
+                (update_cursor_grab, apply_cursor_grab)
+                    .chain()
+                    .after(InputSystems)
+                    .before(ActionSystems),
+            );
+    }
+}
+
+/// Whether the cursor is captured by the game, e.g. for mouse look.
+///
+/// While released, the cursor is free for UI and the camera ignores the mouse.
+#[derive(Resource, Debug, Default)]
+pub struct CursorState {
+    grabbed: bool,
+}
+
+impl CursorState {
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    pub fn grab(&mut self) {
+        self.grabbed = true;
+    }
+
+    pub fn release(&mut self) {
+        self.grabbed = false;
+    }
+}
+
+fn update_cursor_grab(
+    mut focus_reader: MessageReader<WindowFocused>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut mouse_input: ResMut<ButtonInput<MouseButton>>,
+    egui_wants_input: Option<Res<EguiWantsInput>>,
+    primary_window: Single<Entity, With<PrimaryWindow>>,
+    mut cursor_state: ResMut<CursorState>,
+) {
+    let was_grabbed = cursor_state.grabbed;
+
+    for focus in focus_reader.read() {
+        if focus.window == *primary_window {
+            cursor_state.grabbed = focus.focused;
+        }
+    }
+
+    let egui_wants_input = egui_wants_input.is_some_and(|wants| wants.wants_any_input());
+
+    if keyboard_input.just_pressed(KeyCode::Escape) || egui_wants_input {
+        cursor_state.release();
+    } else if !was_grabbed && mouse_input.just_pressed(MouseButton::Left) {
+        // Clicking back into the game recaptures the cursor after it was released. The click
+        // is swallowed, so it doesn't also count as a press, e.g. firing.
+        cursor_state.grab();
+        mouse_input.reset(MouseButton::Left);
+    }
+}
+
+fn apply_cursor_grab(
+    cursor_state: Res<CursorState>,
+    mut cursor_options: Single<&mut CursorOptions, With<PrimaryWindow>>,
+) {
+    if !cursor_state.is_changed() {
+        return;
+    }
+
+    if cursor_state.grabbed {
+        cursor_options.grab_mode = CursorGrabMode::Locked;
+        cursor_options.visible = false;
+    } else {
+        cursor_options.grab_mode = CursorGrabMode::None;
+        cursor_options.visible = true;
+    }
+}
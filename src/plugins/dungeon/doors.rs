@@ -0,0 +1,242 @@
+use avian3d::prelude::{Collider, ColliderDisabled, RigidBody};
+use bevy::{color::palettes::tailwind, platform::collections::HashSet, prelude::*};
+
+use super::{DungeonLayout, TILE_SIZE, Tile, WALL_HEIGHT, tile_center};
+use crate::plugins::{
+    actions::{ActionState, ButtonAction},
+    collision::GameLayer,
+    player::Player,
+    spectator::is_spectating,
+};
+
+pub(super) struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Keyring>()
+            .add_message::<KeyPickedUp>()
+            .add_message::<DoorLocked>()
+            .add_systems(
+                Update,
+                (
+                    pick_up_keys,
+                    use_doors.run_if(not(is_spectating)),
+                    animate_doors,
+                ),
+            );
+    }
+}
+
+/// A door that slides up into the ceiling when used.
+#[derive(Component)]
+pub struct Door {
+    /// The key that unlocks it, as an index into [`DungeonLayout::locks`].
+    pub lock: Option<usize>,
+    pub open: bool,
+    /// How far it has slid up, from 0 when shut to 1 when open.
+    progress: f32,
+}
+
+/// The part of a [`Door`] that moves.
+#[derive(Component)]
+struct DoorPanel;
+
+/// A key lying in the dungeon, waiting to be picked up.
+#[derive(Component)]
+pub struct Key(pub usize);
+
+/// The keys the player has picked up in the current dungeon.
+#[derive(Resource, Default)]
+pub struct Keyring {
+    keys: HashSet<usize>,
+}
+
+impl Keyring {
+    pub fn has(&self, key: usize) -> bool {
+        self.keys.contains(&key)
+    }
+}
+
+/// Sent when the player picks up the key with this index into [`DungeonLayout::locks`].
+#[derive(Message)]
+pub struct KeyPickedUp {
+    pub key: usize,
+}
+
+/// Sent when the player tries a locked door without its key.
+#[derive(Message)]
+pub struct DoorLocked {
+    /// The index of the door, and of the key it needs, into [`DungeonLayout::locks`].
+    pub lock: usize,
+}
+
+/// How thick a door is, in meters.
+const DOOR_THICKNESS: f32 = 0.2;
+
+/// How long a door takes to open or close, in seconds.
+const DOOR_OPEN_TIME: f32 = 0.6;
+
+/// How close the player has to be to a door to use it, in meters.
+const DOOR_REACH: f32 = 2.5;
+
+/// How close the player has to get to a key to pick it up, in meters.
+const KEY_PICKUP_DISTANCE: f32 = 1.2;
+
+/// How fast keys spin, in radians per second.
+const KEY_SPIN_SPEED: f32 = 2.0;
+
+/// Each lock gets a color its door and key share, cycling through these.
+const KEY_COLORS: [Srgba; 5] = [
+    tailwind::AMBER_400,
+    tailwind::SKY_400,
+    tailwind::ROSE_400,
+    tailwind::EMERALD_400,
+    tailwind::VIOLET_400,
+];
+
+/// Spawns a door on every door tile and a key for every lock.
+pub(super) fn spawn_doors_and_keys(
+    parent: &mut ChildSpawnerCommands,
+    layout: &DungeonLayout,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let plain_material = materials.add(Color::from(tailwind::AMBER_900));
+    let key_mesh = meshes.add(Cuboid::new(0.1, 0.1, 0.4));
+
+    for position in layout.positions() {
+        if layout.tile(position) != Tile::Door {
+            continue;
+        }
+
+        // Doors span the passage, between the walls on either side.
+        let size = if layout.is_open(position + IVec2::X) {
+            Vec3::new(DOOR_THICKNESS, WALL_HEIGHT, TILE_SIZE)
+        } else {
+            Vec3::new(TILE_SIZE, WALL_HEIGHT, DOOR_THICKNESS)
+        };
+        let lock = layout.locks.iter().position(|lock| lock.door == position);
+        let material = match lock {
+            Some(lock) => materials.add(Color::from(KEY_COLORS[lock % KEY_COLORS.len()])),
+            None => plain_material.clone(),
+        };
+
+        parent
+            .spawn((
+                Name::new("Door"),
+                Door {
+                    lock,
+                    open: false,
+                    progress: 0.0,
+                },
+                Transform::from_translation(tile_center(position) + Vec3::Y * WALL_HEIGHT * 0.5),
+                Visibility::default(),
+                RigidBody::Static,
+                Collider::cuboid(size.x, size.y, size.z),
+                GameLayer::world(),
+            ))
+            .with_child((
+                DoorPanel,
+                Mesh3d(meshes.add(Cuboid::from_size(size))),
+                MeshMaterial3d(material),
+            ));
+    }
+
+    for (index, lock) in layout.locks.iter().enumerate() {
+        let color = KEY_COLORS[index % KEY_COLORS.len()];
+        parent.spawn((
+            Name::new("Key"),
+            Key(index),
+            Mesh3d(key_mesh.clone()),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: color.into(),
+                emissive: LinearRgba::from(color) * 4.0,
+                ..default()
+            })),
+            Transform::from_translation(tile_center(lock.key) + Vec3::Y),
+        ));
+    }
+}
+
+fn pick_up_keys(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut keyring: ResMut<Keyring>,
+    mut picked_up_writer: MessageWriter<KeyPickedUp>,
+    player: Single<&Transform, With<Player>>,
+    mut keys: Query<(Entity, &Key, &mut Transform, &GlobalTransform), Without<Player>>,
+) {
+    for (entity, key, mut transform, global_transform) in &mut keys {
+        transform.rotate_y(KEY_SPIN_SPEED * time.delta_secs());
+
+        if global_transform.translation().distance(player.translation) < KEY_PICKUP_DISTANCE {
+            keyring.keys.insert(key.0);
+            commands.entity(entity).despawn();
+            picked_up_writer.write(KeyPickedUp { key: key.0 });
+            debug!("Picked up key {}", key.0 + 1);
+        }
+    }
+}
+
+/// Opens or closes the door the player is facing, if they have its key.
+fn use_doors(
+    action_state: Res<ActionState>,
+    keyring: Res<Keyring>,
+    mut locked_writer: MessageWriter<DoorLocked>,
+    player: Single<&Transform, With<Player>>,
+    mut doors: Query<(&mut Door, &GlobalTransform)>,
+) {
+    if !action_state.just_pressed(ButtonAction::Interact) {
+        return;
+    }
+
+    let facing = doors
+        .iter_mut()
+        .filter_map(|(door, global_transform)| {
+            let offset = (global_transform.translation() - player.translation).with_y(0.0);
+            let distance = offset.length();
+            let is_facing = offset.normalize_or_zero().dot(*player.forward()) > 0.5;
+            (distance < DOOR_REACH && is_facing).then_some((door, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    let Some((mut door, _)) = facing else {
+        return;
+    };
+
+    match door.lock {
+        Some(lock) if !keyring.has(lock) => {
+            locked_writer.write(DoorLocked { lock });
+            debug!("Door {} is locked", lock + 1);
+        }
+        _ => door.open = !door.open,
+    }
+}
+
+/// Slides doors towards their open or shut position. Their collider is only turned off
+/// once they are all the way open, and back on as soon as they start to close.
+fn animate_doors(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut doors: Query<(Entity, &mut Door, &Children, Has<ColliderDisabled>)>,
+    mut panels: Query<&mut Transform, With<DoorPanel>>,
+) {
+    for (entity, mut door, children, is_disabled) in &mut doors {
+        let target = if door.open { 1.0 } else { 0.0 };
+        let step = time.delta_secs() / DOOR_OPEN_TIME;
+        door.progress += (target - door.progress).clamp(-step, step);
+
+        let mut panels = panels.iter_many_mut(children);
+        while let Some(mut transform) = panels.fetch_next() {
+            // Ease in and out rather than sliding at a constant speed.
+            let eased = door.progress * door.progress * (3.0 - 2.0 * door.progress);
+            transform.translation.y = eased * WALL_HEIGHT;
+        }
+
+        let should_disable = door.open && door.progress >= 1.0;
+        if should_disable && !is_disabled {
+            commands.entity(entity).insert(ColliderDisabled);
+        } else if !should_disable && is_disabled {
+            commands.entity(entity).remove::<ColliderDisabled>();
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// What occupies one cell of the dungeon grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tile {
+    /// Solid rock. Walls are drawn wherever an open tile borders it.
+    #[default]
+    Solid,
+    Floor,
+    Door,
+    /// Floor with a column in the middle that holds up the ceiling.
+    Pillar,
+}
+
+/// A rectangular room in grid coordinates, `min` inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Room {
+    pub min: IVec2,
+    pub size: IVec2,
+}
+
+impl Room {
+    pub fn max(&self) -> IVec2 {
+        self.min + self.size
+    }
+
+    pub fn center(&self) -> IVec2 {
+        self.min + self.size / 2
+    }
+
+    pub fn contains(&self, position: IVec2) -> bool {
+        position.cmpge(self.min).all() && position.cmplt(self.max()).all()
+    }
+
+    /// Whether the rooms overlap once this one is grown by `margin` tiles on every side.
+    pub fn intersects(&self, other: &Room, margin: i32) -> bool {
+        self.min.x - margin < other.max().x
+            && other.min.x < self.max().x + margin
+            && self.min.y - margin < other.max().y
+            && other.min.y < self.max().y + margin
+    }
+}
+
+/// A door that stays shut until the key lying at `key` has been picked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lock {
+    pub door: IVec2,
+    pub key: IVec2,
+}
+
+/// Where an enemy starts, and the tiles it patrols between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnemySpawn {
+    pub position: IVec2,
+    pub patrol: Vec<IVec2>,
+}
+
+/// A dungeon as plain grid data, independent of how it is rendered.
+///
+/// X and Y on the grid map to X and Z in the world. Layouts are either generated or
+/// loaded from `*.dungeon.ron` files.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct DungeonLayout {
+    pub size: IVec2,
+    tiles: Vec<Tile>,
+    pub rooms: Vec<Room>,
+    /// Pairs of indices into `rooms` joined by a corridor.
+    pub corridors: Vec<(usize, usize)>,
+    /// Where the player starts.
+    pub spawn: IVec2,
+    /// Locked doors and where their keys are. A key opens the door of the same index.
+    pub locks: Vec<Lock>,
+    pub enemies: Vec<EnemySpawn>,
+}
+
+impl DungeonLayout {
+    /// An all solid layout.
+    pub fn new(size: IVec2) -> Self {
+        Self {
+            size,
+            tiles: vec![Tile::Solid; (size.x * size.y).max(0) as usize],
+            rooms: Vec::new(),
+            corridors: Vec::new(),
+            spawn: IVec2::ZERO,
+            locks: Vec::new(),
+            enemies: Vec::new(),
+        }
+    }
+
+    pub fn in_bounds(&self, position: IVec2) -> bool {
+        position.cmpge(IVec2::ZERO).all() && position.cmplt(self.size).all()
+    }
+
+    fn index(&self, position: IVec2) -> usize {
+        (position.y * self.size.x + position.x) as usize
+    }
+
+    /// The tile at `position`. Everything outside the grid is solid.
+    pub fn tile(&self, position: IVec2) -> Tile {
+        if self.in_bounds(position) {
+            self.tiles[self.index(position)]
+        } else {
+            Tile::Solid
+        }
+    }
+
+    pub fn set_tile(&mut self, position: IVec2, tile: Tile) {
+        if self.in_bounds(position) {
+            let index = self.index(position);
+            self.tiles[index] = tile;
+        }
+    }
+
+    /// Whether the tile has a floor and ceiling rather than being solid rock.
+    pub fn is_open(&self, position: IVec2) -> bool {
+        self.tile(position) != Tile::Solid
+    }
+
+    pub fn is_walkable(&self, position: IVec2) -> bool {
+        matches!(self.tile(position), Tile::Floor | Tile::Door)
+    }
+
+    /// Every grid position, row by row.
+    pub fn positions(&self) -> impl Iterator<Item = IVec2> + use<> {
+        let size = self.size;
+        (0..size.y).flat_map(move |y| (0..size.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    /// Whether a door fits at `position` on the way into `room`: it borders the room and
+    /// has rock on both sides.
+    fn is_doorway(&self, position: IVec2, room: &Room) -> bool {
+        [IVec2::X, IVec2::Y].iter().any(|&across| {
+            let along = across.perp();
+            !self.is_open(position + across)
+                && !self.is_open(position - across)
+                && (room.contains(position + along) || room.contains(position - along))
+        })
+    }
+
+    /// Every solid tile next to an open one, including those just outside the grid.
+    pub fn wall_tiles(&self) -> impl Iterator<Item = IVec2> + use<'_> {
+        let size = self.size;
+        (-1..=size.y)
+            .flat_map(move |y| (-1..=size.x).map(move |x| IVec2::new(x, y)))
+            .filter(|&position| {
+                !self.is_open(position)
+                    && [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                        .iter()
+                        .any(|&direction| self.is_open(position + direction))
+            })
+    }
+
+    fn walkable_count(&self) -> usize {
+        self.positions()
+            .filter(|&position| self.is_walkable(position))
+            .count()
+    }
+
+    /// Which tiles can be walked to from `start` without stepping on a `blocked` one,
+    /// indexed like the grid.
+    fn reachable(&self, start: IVec2, blocked: impl Fn(IVec2) -> bool) -> Vec<bool> {
+        let mut visited = vec![false; self.tiles.len()];
+        if !self.is_walkable(start) || blocked(start) {
+            return visited;
+        }
+
+        let mut queue = VecDeque::from([start]);
+        visited[self.index(start)] = true;
+
+        while let Some(position) = queue.pop_front() {
+            for direction in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let next = position + direction;
+                if !self.is_walkable(next) || blocked(next) {
+                    continue;
+                }
+                let index = self.index(next);
+                if !visited[index] {
+                    visited[index] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        visited
+    }
+
+    /// Whether every walkable tile can be reached from every other one.
+    pub fn is_connected(&self) -> bool {
+        let Some(start) = self
+            .positions()
+            .find(|&position| self.is_walkable(position))
+        else {
+            return true;
+        };
+
+        let reached = self.reachable(start, |_| false);
+        reached.iter().filter(|&&reached| reached).count() == self.walkable_count()
+    }
+
+    /// Whether the player can reach every walkable tile from the spawn, picking up keys
+    /// on the way and only going through locked doors once they have the key.
+    pub fn is_solvable(&self) -> bool {
+        let mut collected = vec![false; self.locks.len()];
+
+        loop {
+            let reached = self.reachable(self.spawn, |position| {
+                self.locks
+                    .iter()
+                    .zip(&collected)
+                    .any(|(lock, &collected)| !collected && lock.door == position)
+            });
+
+            let mut picked_up_any = false;
+            for (lock, collected) in self.locks.iter().zip(&mut collected) {
+                if !*collected && self.in_bounds(lock.key) && reached[self.index(lock.key)] {
+                    *collected = true;
+                    picked_up_any = true;
+                }
+            }
+
+            if !picked_up_any {
+                return reached.iter().filter(|&&reached| reached).count() == self.walkable_count();
+            }
+        }
+    }
+}
+
+/// Parameters for [`generate`]. The same settings always give the same dungeon.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct DungeonSettings {
+    /// A layout file to load instead of generating one, relative to the assets folder.
+    pub layout: Option<String>,
+    pub seed: u64,
+    /// Size of the grid, in tiles.
+    pub size: IVec2,
+    /// How many times to try placing a room. Rooms that would overlap are skipped.
+    pub room_attempts: u32,
+    pub min_room_size: i32,
+    pub max_room_size: i32,
+    /// How many doors to lock. Fewer are locked if there aren't enough doors that
+    /// close off part of the dungeon.
+    pub locked_doors: u32,
+    /// How many enemies to place, at most one per room. The spawn room is left empty.
+    pub enemies: u32,
+}
+
+impl Default for DungeonSettings {
+    fn default() -> Self {
+        Self {
+            layout: None,
+            seed: 0,
+            size: IVec2::new(48, 48),
+            room_attempts: 40,
+            min_room_size: 3,
+            max_room_size: 8,
+            locked_doors: 3,
+            enemies: 5,
+        }
+    }
+}
+
+/// Places non-overlapping rooms at random and joins them with corridors along a minimum
+/// spanning tree of their centers, so every room is reachable. Doors go where corridors
+/// meet rooms, and some are locked with their keys placed so the dungeon stays solvable.
+pub fn generate(settings: &DungeonSettings) -> DungeonLayout {
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+    let mut layout = DungeonLayout::new(settings.size);
+
+    let min_size = settings.min_room_size.max(1);
+    let max_size = settings.max_room_size.max(min_size);
+
+    for _ in 0..settings.room_attempts {
+        let size = IVec2::new(
+            rng.random_range(min_size..=max_size),
+            rng.random_range(min_size..=max_size),
+        );
+        // Keep a border of rock around the edge of the grid.
+        let max_min = settings.size - size - IVec2::ONE;
+        if max_min.x < 1 || max_min.y < 1 {
+            continue;
+        }
+        let room = Room {
+            min: IVec2::new(
+                rng.random_range(1..=max_min.x),
+                rng.random_range(1..=max_min.y),
+            ),
+            size,
+        };
+
+        if layout.rooms.iter().any(|other| room.intersects(other, 1)) {
+            continue;
+        }
+        layout.rooms.push(room);
+    }
+
+    for room in layout.rooms.clone() {
+        for y in room.min.y..room.max().y {
+            for x in room.min.x..room.max().x {
+                layout.set_tile(IVec2::new(x, y), Tile::Floor);
+            }
+        }
+    }
+
+    layout.corridors = minimum_spanning_tree(&layout.rooms);
+    let mut paths = Vec::new();
+    for (a, b) in layout.corridors.clone() {
+        let (start, end) = (layout.rooms[a].center(), layout.rooms[b].center());
+        // Bend the corridor one way or the other, so they don't all look alike.
+        let corner = if rng.random_bool(0.5) {
+            IVec2::new(end.x, start.y)
+        } else {
+            IVec2::new(start.x, end.y)
+        };
+        let mut path = line(start, corner);
+        path.extend(line(corner, end).into_iter().skip(1));
+        for &position in &path {
+            layout.set_tile(position, Tile::Floor);
+        }
+        paths.push((b, path));
+    }
+
+    layout.spawn = layout.rooms.first().map_or(settings.size / 2, Room::center);
+    layout.set_tile(layout.spawn, Tile::Floor);
+
+    // Corridors run from the room closer to the spawn to the one further away, so a door
+    // where a corridor enters its far room closes that room off.
+    for (room, path) in paths {
+        let room = layout.rooms[room];
+        let doorway = path
+            .iter()
+            .rev()
+            .find(|&&position| !layout.rooms.iter().any(|room| room.contains(position)))
+            .copied();
+        if let Some(doorway) = doorway.filter(|&doorway| layout.is_doorway(doorway, &room)) {
+            layout.set_tile(doorway, Tile::Door);
+        }
+    }
+
+    place_locks(&mut layout, settings.locked_doors, &mut rng);
+    place_enemies(&mut layout, settings.enemies, &mut rng);
+
+    layout
+}
+
+/// Puts enemies in random rooms other than the first, each patrolling the corners of its room.
+fn place_enemies(layout: &mut DungeonLayout, count: u32, rng: &mut StdRng) {
+    let mut rooms: Vec<Room> = layout.rooms.iter().skip(1).copied().collect();
+
+    for _ in 0..count {
+        if rooms.is_empty() {
+            break;
+        }
+        let room = rooms.swap_remove(rng.random_range(0..rooms.len()));
+        let (min, max) = (room.min, room.max() - IVec2::ONE);
+        layout.enemies.push(EnemySpawn {
+            position: room.center(),
+            patrol: vec![min, IVec2::new(max.x, min.y), max, IVec2::new(min.x, max.y)],
+        });
+    }
+}
+
+/// Locks doors that close off part of the dungeon, and puts each key somewhere that can be
+/// reached before its door.
+fn place_locks(layout: &mut DungeonLayout, count: u32, rng: &mut StdRng) {
+    let walkable_count = layout.walkable_count();
+    let mut pending: Vec<IVec2> = layout
+        .positions()
+        .filter(|&position| {
+            layout.tile(position) == Tile::Door
+                && layout
+                    .reachable(layout.spawn, |other| other == position)
+                    .iter()
+                    .filter(|&&reached| reached)
+                    .count()
+                    < walkable_count - 1
+        })
+        .collect();
+    while pending.len() > count as usize {
+        pending.swap_remove(rng.random_range(0..pending.len()));
+    }
+
+    // Unlock doors one at a time in an order the player could, each key lying in the
+    // part of the dungeon that is open before its door.
+    while !pending.is_empty() {
+        let reached = layout.reachable(layout.spawn, |position| pending.contains(&position));
+        let Some(next) = pending.iter().position(|&door| {
+            [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                .iter()
+                .any(|&direction| {
+                    let neighbor = door + direction;
+                    layout.in_bounds(neighbor) && reached[layout.index(neighbor)]
+                })
+        }) else {
+            break;
+        };
+        let door = pending.remove(next);
+
+        let spots: Vec<IVec2> = layout
+            .positions()
+            .filter(|&position| {
+                reached[layout.index(position)]
+                    && layout.tile(position) == Tile::Floor
+                    && position != layout.spawn
+                    && layout.rooms.iter().any(|room| room.contains(position))
+                    && !layout.locks.iter().any(|lock| lock.key == position)
+            })
+            .collect();
+        if spots.is_empty() {
+            continue;
+        }
+        let key = spots[rng.random_range(0..spots.len())];
+        layout.locks.push(Lock { door, key });
+    }
+}
+
+/// Prim's algorithm over the distances between room centers.
+fn minimum_spanning_tree(rooms: &[Room]) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    if rooms.is_empty() {
+        return edges;
+    }
+
+    let mut in_tree = vec![false; rooms.len()];
+    // The closest room already in the tree, and how far away it is.
+    let mut closest = vec![(0, i32::MAX); rooms.len()];
+    in_tree[0] = true;
+    for (i, room) in rooms.iter().enumerate().skip(1) {
+        closest[i] = (0, room.center().distance_squared(rooms[0].center()));
+    }
+
+    for _ in 1..rooms.len() {
+        let Some(next) = (0..rooms.len())
+            .filter(|&i| !in_tree[i])
+            .min_by_key(|&i| closest[i].1)
+        else {
+            break;
+        };
+        in_tree[next] = true;
+        edges.push((closest[next].0, next));
+
+        for i in 0..rooms.len() {
+            let distance = rooms[i].center().distance_squared(rooms[next].center());
+            if !in_tree[i] && distance < closest[i].1 {
+                closest[i] = (next, distance);
+            }
+        }
+    }
+
+    edges
+}
+
+/// The tiles on a straight horizontal or vertical line from `start` to `end`, inclusive.
+fn line(start: IVec2, end: IVec2) -> Vec<IVec2> {
+    let step = (end - start).signum();
+    let mut position = start;
+    let mut tiles = vec![position];
+    while position != end {
+        position += step;
+        tiles.push(position);
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_dungeons_are_connected() {
+        for seed in 0..200 {
+            let layout = generate(&DungeonSettings { seed, ..default() });
+            assert!(layout.rooms.len() > 1, "seed {seed} has a single room");
+            assert!(layout.is_connected(), "seed {seed} is not connected");
+        }
+    }
+
+    #[test]
+    fn layouts_split_in_two_are_not_connected() {
+        let mut layout = DungeonLayout::new(IVec2::new(7, 3));
+        for x in [1, 2, 4, 5] {
+            layout.set_tile(IVec2::new(x, 1), Tile::Floor);
+        }
+        assert!(!layout.is_connected());
+
+        layout.set_tile(IVec2::new(3, 1), Tile::Floor);
+        assert!(layout.is_connected());
+    }
+
+    #[test]
+    fn generated_dungeons_are_solvable() {
+        let mut locks = 0;
+        for seed in 0..200 {
+            let layout = generate(&DungeonSettings { seed, ..default() });
+            assert!(layout.is_solvable(), "seed {seed} is not solvable");
+            locks += layout.locks.len();
+        }
+        assert!(locks > 0, "no doors were locked");
+    }
+
+    #[test]
+    fn keys_behind_their_own_door_are_unsolvable() {
+        let mut layout = DungeonLayout::new(IVec2::new(7, 3));
+        for x in 1..=5 {
+            layout.set_tile(IVec2::new(x, 1), Tile::Floor);
+        }
+        layout.set_tile(IVec2::new(3, 1), Tile::Door);
+        layout.spawn = IVec2::new(1, 1);
+        layout.locks.push(Lock {
+            door: IVec2::new(3, 1),
+            key: IVec2::new(5, 1),
+        });
+        assert!(!layout.is_solvable());
+
+        layout.locks[0].key = IVec2::new(2, 1);
+        assert!(layout.is_solvable());
+    }
+}
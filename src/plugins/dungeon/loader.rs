@@ -0,0 +1,230 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::Deserialize;
+
+use super::layout::{DungeonLayout, EnemySpawn, Lock, Tile};
+
+/// What a character in a layout file stands for.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum LegendTile {
+    Floor,
+    Wall,
+    Door,
+    /// A door opened by the key with the same number.
+    LockedDoor(u32),
+    /// A floor tile with the key to the locked door with the same number.
+    Key(u32),
+    /// A floor tile where the player starts.
+    Spawn,
+    /// A floor tile with an enemy standing guard.
+    Enemy,
+    Pillar,
+}
+
+/// The contents of a `*.dungeon.ron` file: an ASCII grid and what each character means.
+///
+/// The first row is the far edge of the dungeon, at the smallest Z.
+#[derive(Debug, Deserialize)]
+struct LayoutFile {
+    #[serde(default = "default_legend")]
+    legend: HashMap<char, LegendTile>,
+    rows: Vec<String>,
+}
+
+fn default_legend() -> HashMap<char, LegendTile> {
+    HashMap::from_iter([
+        ('.', LegendTile::Floor),
+        ('#', LegendTile::Wall),
+        (' ', LegendTile::Wall),
+        ('D', LegendTile::Door),
+        ('S', LegendTile::Spawn),
+        ('P', LegendTile::Pillar),
+        ('E', LegendTile::Enemy),
+    ])
+}
+
+impl LayoutFile {
+    fn into_layout(self) -> Result<DungeonLayout> {
+        let width = self
+            .rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut layout = DungeonLayout::new(IVec2::new(width as i32, self.rows.len() as i32));
+        let mut spawn = None;
+        let mut doors = HashMap::<u32, IVec2>::default();
+        let mut keys = HashMap::<u32, IVec2>::default();
+
+        for (y, row) in self.rows.iter().enumerate() {
+            // Short rows are padded with rock.
+            for (x, character) in row.chars().enumerate() {
+                let position = IVec2::new(x as i32, y as i32);
+                let Some(&legend_tile) = self.legend.get(&character) else {
+                    return Err(format!(
+                        "unknown tile '{character}' at row {}, column {}",
+                        y + 1,
+                        x + 1
+                    )
+                    .into());
+                };
+
+                let tile = match legend_tile {
+                    LegendTile::Floor => Tile::Floor,
+                    LegendTile::Wall => Tile::Solid,
+                    LegendTile::Door => Tile::Door,
+                    LegendTile::Pillar => Tile::Pillar,
+                    LegendTile::LockedDoor(id) => {
+                        if doors.insert(id, position).is_some() {
+                            return Err(format!("more than one door locked with key {id}").into());
+                        }
+                        Tile::Door
+                    }
+                    LegendTile::Key(id) => {
+                        if keys.insert(id, position).is_some() {
+                            return Err(format!("more than one key {id}").into());
+                        }
+                        Tile::Floor
+                    }
+                    LegendTile::Enemy => {
+                        layout.enemies.push(EnemySpawn {
+                            position,
+                            patrol: Vec::new(),
+                        });
+                        Tile::Floor
+                    }
+                    LegendTile::Spawn => {
+                        if spawn.replace(position).is_some() {
+                            return Err("layout has more than one spawn".into());
+                        }
+                        Tile::Floor
+                    }
+                };
+                layout.set_tile(position, tile);
+            }
+        }
+
+        layout.spawn = spawn.ok_or("layout has no spawn")?;
+
+        let mut ids: Vec<u32> = doors.keys().chain(keys.keys()).copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for id in ids {
+            let (Some(&door), Some(&key)) = (doors.get(&id), keys.get(&id)) else {
+                return Err(format!("key {id} needs both a locked door and a key").into());
+            };
+            layout.locks.push(Lock { door, key });
+        }
+
+        Ok(layout)
+    }
+}
+
+#[derive(TypePath, Default)]
+pub struct DungeonLayoutLoader;
+
+impl AssetLoader for DungeonLayoutLoader {
+    type Asset = DungeonLayout;
+    type Settings = ();
+    type Error = BevyError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<DungeonLayout> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file: LayoutFile = ron::de::from_bytes(&bytes)?;
+        file.into_layout()
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dungeon.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses rows with the default legend, plus locked door `1` and its key `a`.
+    fn parse(rows: &[&str]) -> Result<DungeonLayout> {
+        let mut legend = default_legend();
+        legend.insert('1', LegendTile::LockedDoor(1));
+        legend.insert('a', LegendTile::Key(1));
+        LayoutFile {
+            legend,
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+        }
+        .into_layout()
+    }
+
+    fn error(rows: &[&str]) -> String {
+        parse(rows).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_tiles_and_pads_short_rows_with_rock() {
+        let layout = parse(&["#####", "#S.P#", "#ED", "#"]).unwrap();
+
+        assert_eq!(layout.size, IVec2::new(5, 4));
+        assert_eq!(layout.spawn, IVec2::new(1, 1));
+        assert_eq!(layout.tile(IVec2::new(1, 1)), Tile::Floor);
+        assert_eq!(layout.tile(IVec2::new(3, 1)), Tile::Pillar);
+        assert_eq!(layout.tile(IVec2::new(2, 2)), Tile::Door);
+        assert_eq!(layout.tile(IVec2::new(4, 2)), Tile::Solid);
+        assert_eq!(layout.tile(IVec2::new(1, 3)), Tile::Solid);
+        assert_eq!(layout.enemies.len(), 1);
+        assert_eq!(layout.enemies[0].position, IVec2::new(1, 2));
+    }
+
+    #[test]
+    fn rejects_unknown_tiles() {
+        assert!(error(&["#S?#"]).contains("unknown tile '?' at row 1, column 3"));
+    }
+
+    #[test]
+    fn needs_exactly_one_spawn() {
+        assert!(error(&["#..#"]).contains("no spawn"));
+        assert!(error(&["#S.S#"]).contains("more than one spawn"));
+    }
+
+    #[test]
+    fn pairs_locked_doors_with_their_keys() {
+        let layout = parse(&["#S.1.a#"]).unwrap();
+        assert_eq!(
+            layout.locks,
+            vec![Lock {
+                door: IVec2::new(3, 0),
+                key: IVec2::new(5, 0),
+            }]
+        );
+        assert_eq!(layout.tile(IVec2::new(3, 0)), Tile::Door);
+        assert_eq!(layout.tile(IVec2::new(5, 0)), Tile::Floor);
+    }
+
+    #[test]
+    fn rejects_unpaired_or_repeated_locks() {
+        assert!(error(&["#S.1..#"]).contains("key 1 needs both a locked door and a key"));
+        assert!(error(&["#S...a#"]).contains("key 1 needs both a locked door and a key"));
+        assert!(error(&["#S1.1a#"]).contains("more than one door locked with key 1"));
+        assert!(error(&["#Sa.1a#"]).contains("more than one key 1"));
+    }
+
+    #[test]
+    fn crypt_layout_is_solvable() {
+        let file: LayoutFile =
+            ron::de::from_str(include_str!("../../../assets/dungeons/crypt.dungeon.ron")).unwrap();
+        let layout = file.into_layout().unwrap();
+
+        assert_eq!(layout.locks.len(), 2);
+        assert_eq!(layout.enemies.len(), 2);
+        assert!(layout.is_connected());
+        assert!(layout.is_solvable());
+    }
+}
@@ -0,0 +1,506 @@
+mod doors;
+mod layout;
+mod loader;
+
+use avian3d::prelude::{Collider, LinearVelocity, Position, RigidBody, Rotation};
+use bevy::{
+    asset::RenderAssetUsages,
+    camera::visibility::RenderLayers,
+    color::palettes::tailwind,
+    mesh::{Indices, PrimitiveTopology},
+    prelude::*,
+};
+
+use crate::plugins::{
+    actions::{ActionState, ButtonAction},
+    collision::GameLayer,
+    enemy::enemy,
+    navigation::NavMeshVolume,
+    player::{PLAYER_BODY_RENDER_LAYER, Player, VIEW_MODEL_RENDER_LAYER},
+};
+pub use doors::{DoorLocked, KeyPickedUp};
+use doors::{DoorPlugin, Keyring, spawn_doors_and_keys};
+pub use layout::{DungeonLayout, DungeonSettings, Tile, generate};
+use loader::DungeonLayoutLoader;
+
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(DoorPlugin)
+            .init_asset::<DungeonLayout>()
+            .init_asset_loader::<DungeonLayoutLoader>()
+            .init_resource::<DungeonSettings>()
+            .init_resource::<ActiveLayout>()
+            .add_message::<EnterDungeon>()
+            .add_systems(
+                Update,
+                (
+                    reroll_dungeon,
+                    select_layout,
+                    load_layout_file,
+                    build_dungeon,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Where the corner of the dungeon grid sits, clear of the testbed.
+const DUNGEON_ORIGIN: Vec3 = Vec3::new(40.0, 0.0, 0.0);
+
+/// Width of one grid tile, in meters.
+const TILE_SIZE: f32 = 2.0;
+
+const WALL_HEIGHT: f32 = 3.0;
+
+/// Width of a pillar as a fraction of its tile.
+const PILLAR_WIDTH: f32 = 0.4;
+
+/// Ceiling lights are placed on open tiles at this spacing, in tiles.
+const LIGHT_SPACING: i32 = 4;
+
+/// The root of the generated dungeon. Everything it spawns is a child, so it can be
+/// despawned in one go when regenerating.
+#[derive(Component)]
+pub struct Dungeon;
+
+/// The layout the dungeon is built from, and the file it came from if it was loaded.
+#[derive(Resource, Default)]
+pub struct ActiveLayout {
+    pub layout: Option<DungeonLayout>,
+    file: Option<Handle<DungeonLayout>>,
+}
+
+/// Moves the player to the entrance of the dungeon when it is next rebuilt.
+///
+/// Only sent when the player asks for a new dungeon, so that building the first one, or
+/// tweaking the settings, doesn't pull them out of the testbed.
+#[derive(Message)]
+struct EnterDungeon;
+
+/// Picks the next seed, which regenerates the dungeon, and takes the player into it.
+fn reroll_dungeon(
+    action_state: Res<ActionState>,
+    mut settings: ResMut<DungeonSettings>,
+    mut enter_writer: MessageWriter<EnterDungeon>,
+) {
+    if action_state.just_pressed(ButtonAction::RegenerateDungeon) {
+        settings.seed = settings.seed.wrapping_add(1);
+        enter_writer.write(EnterDungeon);
+    }
+}
+
+/// Generates a new layout, or starts loading the layout file, whenever the settings
+/// change, including when they are first added.
+fn select_layout(
+    settings: Res<DungeonSettings>,
+    asset_server: Res<AssetServer>,
+    mut active_layout: ResMut<ActiveLayout>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    match &settings.layout {
+        Some(path) => {
+            // The layout is picked up by `load_layout_file` once it has loaded.
+            active_layout.file = Some(asset_server.load(path));
+        }
+        None => {
+            let layout = generate(&settings);
+            warn_if_unplayable(&layout);
+            active_layout.file = None;
+            active_layout.layout = Some(layout);
+        }
+    }
+}
+
+/// Uses the layout file once it loads, and again every time it is saved. Saving it takes
+/// the player back to the entrance.
+fn load_layout_file(
+    mut asset_events: MessageReader<AssetEvent<DungeonLayout>>,
+    layouts: Res<Assets<DungeonLayout>>,
+    mut active_layout: ResMut<ActiveLayout>,
+    mut enter_writer: MessageWriter<EnterDungeon>,
+) {
+    for event in asset_events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = *event
+        else {
+            continue;
+        };
+        if active_layout
+            .file
+            .as_ref()
+            .is_none_or(|file| file.id() != id)
+        {
+            continue;
+        }
+
+        if let Some(layout) = layouts.get(id) {
+            warn_if_unplayable(layout);
+            active_layout.layout = Some(layout.clone());
+            if matches!(event, AssetEvent::Modified { .. }) {
+                enter_writer.write(EnterDungeon);
+            }
+        }
+    }
+}
+
+/// Warns about parts of the layout the player can't get to. The dungeon is still built, so
+/// the rest of it can be played.
+fn warn_if_unplayable(layout: &DungeonLayout) {
+    if !layout.is_connected() {
+        warn!("Dungeon layout has walkable areas that can't be reached");
+    } else if !layout.is_solvable() {
+        warn!("Dungeon layout has locked doors whose keys can't be reached");
+    }
+}
+
+/// Rebuilds the dungeon whenever the active layout changes.
+#[allow(clippy::type_complexity)]
+fn build_dungeon(
+    mut commands: Commands,
+    mut enter_reader: MessageReader<EnterDungeon>,
+    active_layout: Res<ActiveLayout>,
+    dungeons: Query<Entity, With<Dungeon>>,
+    player: Option<Single<(&mut Position, &mut Transform, &mut LinearVelocity), With<Player>>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Read even when nothing changed, so a stale request can't move the player later.
+    let enter = enter_reader.read().count() > 0;
+
+    if !active_layout.is_changed() {
+        return;
+    }
+    let Some(layout) = &active_layout.layout else {
+        return;
+    };
+
+    for dungeon in &dungeons {
+        commands.entity(dungeon).despawn();
+    }
+    commands.insert_resource(Keyring::default());
+
+    let floor_material = materials.add(Color::from(tailwind::STONE_500));
+    let wall_material = materials.add(Color::from(tailwind::STONE_400));
+    let ceiling_material = materials.add(Color::from(tailwind::STONE_700));
+    let pillar_material = materials.add(Color::from(tailwind::STONE_300));
+
+    let surfaces = DungeonSurfaces::from_layout(layout);
+
+    commands
+        .spawn((
+            Name::new("Dungeon"),
+            Dungeon,
+            Transform::from_translation(DUNGEON_ORIGIN),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            for (name, surface, material) in [
+                ("Floor", surfaces.floor, floor_material),
+                ("Walls", surfaces.walls, wall_material),
+                ("Ceiling", surfaces.ceiling, ceiling_material),
+                ("Pillars", surfaces.pillars, pillar_material),
+            ] {
+                if surface.is_empty() {
+                    continue;
+                }
+                parent.spawn((
+                    Name::new(name),
+                    RigidBody::Static,
+                    surface.collider(),
+                    GameLayer::world(),
+                    Mesh3d(meshes.add(surface.mesh())),
+                    MeshMaterial3d(material),
+                ));
+            }
+
+            spawn_doors_and_keys(parent, layout, &mut meshes, &mut materials);
+
+            for spawn in &layout.enemies {
+                let patrol = spawn
+                    .patrol
+                    .iter()
+                    .map(|&point| DUNGEON_ORIGIN + tile_center(point) + Vec3::Y * 1.0)
+                    .collect();
+                parent.spawn(enemy(
+                    tile_center(spawn.position) + Vec3::Y * 1.0,
+                    patrol,
+                    &mut meshes,
+                    &mut materials,
+                ));
+            }
+
+            // From just below the floor to just below the ceiling, so the top of the
+            // ceiling isn't mistaken for floor.
+            let extent = layout.size.as_vec2() * TILE_SIZE;
+            parent.spawn((
+                Name::new("NavMesh"),
+                NavMeshVolume::new(Vec3::new(
+                    extent.x * 0.5,
+                    (WALL_HEIGHT + 0.4) * 0.5,
+                    extent.y * 0.5,
+                )),
+                Transform::from_xyz(extent.x * 0.5, (WALL_HEIGHT - 0.6) * 0.5, extent.y * 0.5),
+            ));
+
+            let lights = layout.positions().filter(|&position| {
+                position % LIGHT_SPACING == IVec2::splat(LIGHT_SPACING / 2)
+                    && layout.is_walkable(position)
+            });
+            for position in lights {
+                parent.spawn((
+                    PointLight {
+                        color: Color::from(tailwind::ORANGE_200),
+                        intensity: 400_000.0,
+                        range: TILE_SIZE * LIGHT_SPACING as f32 * 1.5,
+                        ..default()
+                    },
+                    Transform::from_translation(
+                        tile_center(position) + Vec3::Y * (WALL_HEIGHT - 0.5),
+                    ),
+                    RenderLayers::from_layers(&[
+                        0,
+                        VIEW_MODEL_RENDER_LAYER,
+                        PLAYER_BODY_RENDER_LAYER,
+                    ]),
+                ));
+            }
+        });
+
+    // Put the player at the entrance of the new dungeon.
+    if enter && let Some(player) = player {
+        let (mut position, mut transform, mut linear_velocity) = player.into_inner();
+        let spawn = DUNGEON_ORIGIN + tile_center(layout.spawn) + Vec3::Y * 1.0;
+        position.0 = spawn;
+        transform.translation = spawn;
+        linear_velocity.0 = Vec3::ZERO;
+    }
+}
+
+/// The center of a tile on the floor, relative to the dungeon origin.
+fn tile_center(position: IVec2) -> Vec3 {
+    Vec3::new(
+        (position.x as f32 + 0.5) * TILE_SIZE,
+        0.0,
+        (position.y as f32 + 0.5) * TILE_SIZE,
+    )
+}
+
+/// The floor, walls, ceiling and pillars of a layout, each merged into a single surface.
+struct DungeonSurfaces {
+    floor: Surface,
+    walls: Surface,
+    ceiling: Surface,
+    pillars: Surface,
+}
+
+impl DungeonSurfaces {
+    fn from_layout(layout: &DungeonLayout) -> Self {
+        let mut surfaces = Self {
+            floor: Surface::default(),
+            walls: Surface::default(),
+            ceiling: Surface::default(),
+            pillars: Surface::default(),
+        };
+
+        for position in layout.positions() {
+            if !layout.is_open(position) {
+                continue;
+            }
+
+            let corner = Vec3::new(position.x as f32, 0.0, position.y as f32) * TILE_SIZE;
+            let x = Vec3::X * TILE_SIZE;
+            let z = Vec3::Z * TILE_SIZE;
+            let up = Vec3::Y * WALL_HEIGHT;
+
+            surfaces.floor.add_quad(corner, z, x);
+            surfaces.ceiling.add_quad(corner + up, x, z);
+
+            // A wall on every edge facing rock, pointing back into this tile.
+            if !layout.is_open(position + IVec2::X) {
+                surfaces.walls.add_quad(corner + x, z, up);
+            }
+            if !layout.is_open(position + IVec2::NEG_X) {
+                surfaces.walls.add_quad(corner + z, -z, up);
+            }
+            if !layout.is_open(position + IVec2::Y) {
+                surfaces.walls.add_quad(corner + x + z, -x, up);
+            }
+            if !layout.is_open(position + IVec2::NEG_Y) {
+                surfaces.walls.add_quad(corner, x, up);
+            }
+
+            if layout.tile(position) == Tile::Pillar {
+                surfaces.pillars.add_pillar(
+                    corner + (x + z) * (1.0 - PILLAR_WIDTH) * 0.5,
+                    x * PILLAR_WIDTH,
+                    z * PILLAR_WIDTH,
+                    up,
+                );
+            }
+        }
+
+        // Solid boxes collide more reliably than the thin wall quads.
+        for position in layout.wall_tiles() {
+            surfaces.walls.add_collision_box(
+                tile_center(position) + Vec3::Y * WALL_HEIGHT * 0.5,
+                Vec3::new(TILE_SIZE, WALL_HEIGHT, TILE_SIZE),
+            );
+        }
+
+        surfaces
+    }
+}
+
+/// Quads merged into one mesh and collider.
+#[derive(Default)]
+struct Surface {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    indices: Vec<u32>,
+    /// Centers and sizes of boxes that make up the collider instead of the quads, if any.
+    collision_boxes: Vec<(Vec3, Vec3)>,
+}
+
+impl Surface {
+    fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Adds the quad spanned by `u` and `v` from `corner`, facing along `u × v`.
+    fn add_quad(&mut self, corner: Vec3, u: Vec3, v: Vec3) {
+        let start = self.positions.len() as u32;
+        let normal = u.cross(v).normalize();
+
+        self.positions
+            .extend([corner, corner + u, corner + u + v, corner + v]);
+        self.normals.extend([normal; 4]);
+        self.uvs.extend([
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 0.0),
+        ]);
+        self.indices
+            .extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    /// Adds the four outward facing sides of the box spanned by `x`, `z` and `up`, and
+    /// the box itself to the collider.
+    fn add_pillar(&mut self, corner: Vec3, x: Vec3, z: Vec3, up: Vec3) {
+        self.add_quad(corner + x, -x, up);
+        self.add_quad(corner + x + z, -z, up);
+        self.add_quad(corner + z, x, up);
+        self.add_quad(corner, z, up);
+        self.add_collision_box(corner + (x + z + up) * 0.5, x + z + up);
+    }
+
+    fn add_collision_box(&mut self, center: Vec3, size: Vec3) {
+        self.collision_boxes.push((center, size));
+    }
+
+    fn mesh(&self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions.clone())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals.clone())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs.clone())
+        .with_inserted_indices(Indices::U32(self.indices.clone()))
+    }
+
+    fn collider(&self) -> Collider {
+        if !self.collision_boxes.is_empty() {
+            return Collider::compound(
+                self.collision_boxes
+                    .iter()
+                    .map(|&(center, size)| {
+                        (
+                            center,
+                            Rotation::default(),
+                            Collider::cuboid(size.x, size.y, size.z),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+
+        Collider::trimesh(
+            self.positions.clone(),
+            self.indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::platform::collections::HashSet;
+
+    use super::*;
+
+    /// The solid tiles, in or just outside the grid, next to an open one.
+    fn expected_walls(layout: &DungeonLayout) -> HashSet<IVec2> {
+        layout
+            .positions()
+            .filter(|&position| layout.is_open(position))
+            .flat_map(|position| {
+                [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                    .map(|direction| position + direction)
+            })
+            .filter(|&neighbor| !layout.is_open(neighbor))
+            .collect()
+    }
+
+    fn assert_every_wall_collides(layout: &DungeonLayout) {
+        let expected = expected_walls(layout);
+        assert_eq!(layout.wall_tiles().collect::<HashSet<_>>(), expected);
+
+        let boxes = DungeonSurfaces::from_layout(layout).walls.collision_boxes;
+        assert_eq!(boxes.len(), expected.len());
+        let collided: HashSet<IVec2> = boxes
+            .iter()
+            .map(|&(center, size)| {
+                assert_eq!(size, Vec3::new(TILE_SIZE, WALL_HEIGHT, TILE_SIZE));
+                (center.xz() / TILE_SIZE).floor().as_ivec2()
+            })
+            .collect();
+        assert_eq!(collided, expected);
+    }
+
+    #[test]
+    fn every_wall_tile_of_generated_dungeons_has_a_collider() {
+        for seed in 0..20 {
+            assert_every_wall_collides(&generate(&DungeonSettings { seed, ..default() }));
+        }
+    }
+
+    #[test]
+    fn walls_outside_the_grid_have_colliders() {
+        // Open tiles on every edge, with rock inside the grid as well.
+        let mut layout = DungeonLayout::new(IVec2::new(3, 2));
+        for position in [IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(1, 1)] {
+            layout.set_tile(position, Tile::Floor);
+        }
+        layout.set_tile(IVec2::new(2, 1), Tile::Pillar);
+
+        assert_every_wall_collides(&layout);
+        assert!(
+            layout
+                .wall_tiles()
+                .any(|position| position == IVec2::new(-1, 0))
+        );
+        assert!(
+            layout
+                .wall_tiles()
+                .any(|position| position == IVec2::new(0, 1))
+        );
+    }
+}
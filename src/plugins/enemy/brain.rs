@@ -0,0 +1,440 @@
+use std::f32::consts::FRAC_PI_4;
+
+use avian3d::prelude::Position;
+use bevy::prelude::*;
+
+use super::perception::Memory;
+use crate::plugins::{
+    camera_effects::CameraShake,
+    character_controller::{MovementAction, MovementMessage},
+    combat::Health,
+    navigation::NavMeshes,
+    player::Player,
+};
+
+/// What an enemy is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnemyState {
+    /// Standing still, before moving on to the next patrol point.
+    #[default]
+    Idle,
+    Patrol,
+    /// Heading for where the player was last seen or heard.
+    Chase,
+    Attack,
+    /// Running away from the player while badly hurt.
+    Flee,
+}
+
+/// Decides what an enemy does, and steers it there along the navmesh.
+#[derive(Component)]
+pub struct Brain {
+    pub state: EnemyState,
+    /// Seconds since entering the current state.
+    state_time: f32,
+    /// Points to walk between while patrolling, in world space.
+    patrol: Vec<Vec3>,
+    next_patrol_point: usize,
+    path: Vec<Vec3>,
+    destination: Option<Vec3>,
+    repath_timer: f32,
+}
+
+impl Brain {
+    pub fn new(patrol: Vec<Vec3>) -> Self {
+        Self {
+            state: EnemyState::Idle,
+            state_time: 0.0,
+            patrol,
+            next_patrol_point: 0,
+            path: Vec::new(),
+            destination: None,
+            repath_timer: 0.0,
+        }
+    }
+
+    fn set_state(&mut self, state: EnemyState) {
+        if self.state != state {
+            self.state = state;
+            self.state_time = 0.0;
+            self.destination = None;
+            self.path.clear();
+        }
+    }
+
+    /// The horizontal direction to walk in to follow a path to `destination`.
+    fn steer(&mut self, navmeshes: &NavMeshes, position: Vec3, destination: Vec3) -> Vec3 {
+        let has_moved = self
+            .destination
+            .is_none_or(|previous| previous.distance(destination) > REPATH_DISTANCE);
+        if has_moved || self.repath_timer <= 0.0 {
+            // Without a path, head straight there and hope for the best.
+            self.path = navmeshes
+                .find_path(position, destination)
+                .unwrap_or_else(|| vec![destination]);
+            self.destination = Some(destination);
+            self.repath_timer = REPATH_INTERVAL;
+        }
+
+        while self.path.len() > 1 && horizontal_distance(self.path[0], position) < WAYPOINT_RADIUS {
+            self.path.remove(0);
+        }
+        let waypoint = self.path.first().copied().unwrap_or(destination);
+        (waypoint - position).with_y(0.0).normalize_or_zero()
+    }
+}
+
+/// How an enemy hits the player once it is close enough.
+#[derive(Component)]
+pub struct MeleeAttack {
+    pub range: f32,
+    /// Seconds between hits.
+    pub cooldown: f32,
+    /// How hard a hit knocks the player back, as an impulse in newton seconds. Heavier
+    /// targets are knocked back less.
+    pub knockback: f32,
+    timer: f32,
+}
+
+impl Default for MeleeAttack {
+    fn default() -> Self {
+        Self {
+            range: 1.6,
+            cooldown: 1.2,
+            knockback: 560.0,
+            timer: 0.0,
+        }
+    }
+}
+
+/// How long an enemy waits at each patrol point, in seconds.
+const IDLE_TIME: f32 = 2.0;
+
+/// Enemies flee once their health drops to this fraction.
+const FLEE_HEALTH_FRACTION: f32 = 0.3;
+
+/// How far away from the player a fleeing enemy tries to get, in meters.
+const FLEE_DISTANCE: f32 = 12.0;
+
+/// How far the attack range stretches once attacking, so enemies don't flicker between
+/// chasing and attacking at the edge of it.
+const ATTACK_RANGE_HYSTERESIS: f32 = 1.25;
+
+/// How often paths are recomputed while following them, in seconds.
+const REPATH_INTERVAL: f32 = 0.5;
+
+/// How far the destination can move before the path is recomputed right away.
+const REPATH_DISTANCE: f32 = 1.0;
+
+/// How close a waypoint has to be before moving on to the next one.
+const WAYPOINT_RADIUS: f32 = 0.4;
+
+/// How close counts as having arrived at a destination.
+const ARRIVE_DISTANCE: f32 = 0.6;
+
+/// How fast enemies turn to face where they are going.
+const TURN_SPEED: f32 = 8.0;
+
+/// Patrolling is a walk rather than a run.
+const PATROL_MAGNITUDE: f32 = 0.5;
+
+/// Upward impulse added to the knockback of a hit, in newton seconds.
+const KNOCKBACK_LIFT: f32 = 240.0;
+
+const HIT_TRAUMA: f32 = 0.4;
+
+fn horizontal_distance(a: Vec3, b: Vec3) -> f32 {
+    (a - b).xz().length()
+}
+
+/// Picks each enemy's state from what it knows and how hurt it is.
+pub(super) fn think(
+    time: Res<Time>,
+    player: Single<&Position, With<Player>>,
+    mut enemies: Query<(&mut Brain, &Memory, &Health, &MeleeAttack, &Position)>,
+) {
+    for (mut brain, memory, health, attack, position) in &mut enemies {
+        brain.state_time += time.delta_secs();
+
+        let range = if brain.state == EnemyState::Attack {
+            attack.range * ATTACK_RANGE_HYSTERESIS
+        } else {
+            attack.range
+        };
+        let in_range = memory.sees_player && position.distance(player.0) <= range;
+        let is_wounded = health.fraction() <= FLEE_HEALTH_FRACTION;
+        let knows_where_player_is = memory.last_known_position.is_some();
+
+        let state = match brain.state {
+            _ if is_wounded && knows_where_player_is => EnemyState::Flee,
+            EnemyState::Flee => EnemyState::Idle,
+            _ if in_range => EnemyState::Attack,
+            _ if knows_where_player_is => EnemyState::Chase,
+            EnemyState::Chase | EnemyState::Attack => EnemyState::Idle,
+            EnemyState::Idle if brain.state_time > IDLE_TIME && !brain.patrol.is_empty() => {
+                EnemyState::Patrol
+            }
+            state => state,
+        };
+        brain.set_state(state);
+    }
+}
+
+/// Carries out each enemy's state by sending it movement, the same way the player's input
+/// does, and by attacking.
+#[allow(clippy::type_complexity)]
+pub(super) fn act(
+    time: Res<Time>,
+    navmeshes: NavMeshes,
+    mut movement_writer: MessageWriter<MovementMessage>,
+    mut shake_writer: MessageWriter<CameraShake>,
+    player: Single<(Entity, &Position), With<Player>>,
+    mut enemies: Query<
+        (
+            Entity,
+            &mut Brain,
+            &mut MeleeAttack,
+            &Memory,
+            &Position,
+            &mut Transform,
+        ),
+        Without<Player>,
+    >,
+) {
+    let (player_entity, player_position) = *player;
+    let delta_time = time.delta_secs();
+
+    for (entity, mut brain, mut attack, memory, position, mut transform) in &mut enemies {
+        let position = position.0;
+        brain.repath_timer -= delta_time;
+        attack.timer -= delta_time;
+
+        let mut direction = Vec3::ZERO;
+        let mut magnitude = 1.0;
+        let mut sprint = false;
+        let mut look_at = None;
+
+        match brain.state {
+            EnemyState::Idle => {}
+            EnemyState::Patrol => {
+                let point = brain.patrol[brain.next_patrol_point];
+                if horizontal_distance(point, position) < ARRIVE_DISTANCE {
+                    brain.next_patrol_point = (brain.next_patrol_point + 1) % brain.patrol.len();
+                    brain.set_state(EnemyState::Idle);
+                } else {
+                    direction = brain.steer(&navmeshes, position, point);
+                    magnitude = PATROL_MAGNITUDE;
+                }
+            }
+            EnemyState::Chase => {
+                if let Some(target) = memory.last_known_position
+                    && horizontal_distance(target, position) > ARRIVE_DISTANCE
+                {
+                    direction = brain.steer(&navmeshes, position, target);
+                    sprint = memory.sees_player;
+                }
+                if memory.sees_player {
+                    look_at = memory.last_known_position;
+                }
+            }
+            EnemyState::Attack => {
+                look_at = Some(player_position.0);
+                if attack.timer <= 0.0 {
+                    attack.timer = attack.cooldown;
+                    let away = (player_position.0 - position)
+                        .with_y(0.0)
+                        .normalize_or_zero();
+                    movement_writer.write(MovementMessage::new(
+                        player_entity,
+                        MovementAction::Knockback(
+                            away * attack.knockback + Vec3::Y * KNOCKBACK_LIFT,
+                        ),
+                    ));
+                    shake_writer.write(CameraShake { trauma: HIT_TRAUMA });
+                }
+            }
+            EnemyState::Flee => {
+                if brain.destination.is_none()
+                    && let Some(threat) = memory.last_known_position
+                {
+                    let away = (position - threat)
+                        .with_y(0.0)
+                        .normalize_or(*transform.back());
+                    // Try straight away first, then veer off to either side.
+                    let refuge = [
+                        0.0,
+                        FRAC_PI_4,
+                        -FRAC_PI_4,
+                        2.0 * FRAC_PI_4,
+                        -2.0 * FRAC_PI_4,
+                    ]
+                    .into_iter()
+                    .map(|angle| position + Quat::from_rotation_y(angle) * away * FLEE_DISTANCE)
+                    .find(|refuge| navmeshes.find_path(position, *refuge).is_some())
+                    .unwrap_or(position + away * FLEE_DISTANCE);
+                    brain.destination = Some(refuge);
+                }
+                if let Some(refuge) = brain.destination
+                    && horizontal_distance(refuge, position) > ARRIVE_DISTANCE
+                {
+                    direction = brain.steer(&navmeshes, position, refuge);
+                    sprint = true;
+                } else if memory.sees_player {
+                    // Cornered, or still in sight. Find somewhere else to run to.
+                    brain.destination = None;
+                }
+            }
+        }
+
+        // Face the player when engaging them, otherwise where it is going.
+        let facing = look_at
+            .map(|target| (target - position).with_y(0.0))
+            .unwrap_or(direction);
+        if let Ok(facing) = Dir3::new(facing) {
+            let target = Transform::default().looking_to(facing, Vec3::Y).rotation;
+            transform.rotation = transform
+                .rotation
+                .slerp(target, 1.0 - (-TURN_SPEED * delta_time).exp());
+        }
+
+        movement_writer.write(MovementMessage::new(
+            entity,
+            MovementAction::IsSprinting(sprint),
+        ));
+        movement_writer.write(MovementMessage::new(
+            entity,
+            MovementAction::Move {
+                direction: Vec2::new(direction.x, direction.z),
+                magnitude: if direction == Vec3::ZERO {
+                    0.0
+                } else {
+                    magnitude
+                },
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{ecs::message::Messages, time::TimeUpdateStrategy};
+
+    use super::*;
+
+    /// How much time passes on each update, in seconds.
+    const STEP: f32 = 0.1;
+
+    /// An app that only runs the enemies' decisions. What they know and where everyone is
+    /// is left to the test.
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_message::<MovementMessage>()
+            .add_message::<CameraShake>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                STEP,
+            )))
+            .add_systems(Update, (think, act).chain());
+        app
+    }
+
+    /// Runs the app for `seconds`, returning the movement sent on the last update.
+    fn run(app: &mut App, seconds: f32) -> Vec<MovementMessage> {
+        let mut sent = Vec::new();
+        for _ in 0..(seconds / STEP).round() as u32 {
+            app.update();
+            sent = app
+                .world_mut()
+                .resource_mut::<Messages<MovementMessage>>()
+                .drain()
+                .collect();
+        }
+        sent
+    }
+
+    /// The direction and magnitude of the move sent to `entity`, and whether it sprints.
+    fn movement(sent: &[MovementMessage], entity: Entity) -> (Vec2, f32, bool) {
+        let mut movement = (Vec2::ZERO, 0.0, false);
+        for message in sent.iter().filter(|message| message.entity == entity) {
+            match message.action {
+                MovementAction::Move {
+                    direction,
+                    magnitude,
+                } => (movement.0, movement.1) = (direction, magnitude),
+                MovementAction::IsSprinting(sprint) => movement.2 = sprint,
+                _ => {}
+            }
+        }
+        movement
+    }
+
+    #[test]
+    fn enemies_patrol_chase_attack_and_flee_when_wounded() {
+        let mut app = app();
+        let player_start = Vec3::new(0.0, 0.0, -8.0);
+        let player = app.world_mut().spawn((Player, Position(player_start))).id();
+        let enemy = app
+            .world_mut()
+            .spawn((
+                Brain::new(vec![Vec3::new(6.0, 0.0, 0.0)]),
+                MeleeAttack::default(),
+                Memory::default(),
+                Health::new(100.0),
+                Position(Vec3::ZERO),
+                Transform::default(),
+            ))
+            .id();
+        let state = |app: &App| app.world().get::<Brain>(enemy).unwrap().state;
+
+        // Waits a while, then walks off to its patrol point.
+        run(&mut app, IDLE_TIME * 0.5);
+        assert_eq!(state(&app), EnemyState::Idle);
+        let sent = run(&mut app, IDLE_TIME);
+        assert_eq!(state(&app), EnemyState::Patrol);
+        let (direction, magnitude, sprint) = movement(&sent, enemy);
+        assert!(direction.distance(Vec2::X) < 1e-3, "{direction}");
+        assert_eq!(magnitude, PATROL_MAGNITUDE);
+        assert!(!sprint);
+
+        // Runs at the player once it sees them.
+        let mut memory = app.world_mut().get_mut::<Memory>(enemy).unwrap();
+        memory.sees_player = true;
+        memory.last_known_position = Some(player_start);
+        let sent = run(&mut app, STEP);
+        assert_eq!(state(&app), EnemyState::Chase);
+        let (direction, magnitude, sprint) = movement(&sent, enemy);
+        assert!(direction.distance(Vec2::NEG_Y) < 1e-3, "{direction}");
+        assert_eq!(magnitude, 1.0);
+        assert!(sprint);
+
+        // Hits the player once in range, knocking them away and up.
+        app.world_mut().get_mut::<Position>(player).unwrap().0 = Vec3::new(0.0, 0.0, -1.0);
+        let sent = run(&mut app, STEP);
+        assert_eq!(state(&app), EnemyState::Attack);
+        let knockback = sent
+            .iter()
+            .find_map(|message| match message.action {
+                MovementAction::Knockback(impulse) if message.entity == player => Some(impulse),
+                _ => None,
+            })
+            .expect("the player wasn't hit");
+        assert!(knockback.z < 0.0 && knockback.y > 0.0, "{knockback}");
+
+        // Badly hurt, it runs away from where it last saw the player instead of fighting.
+        app.world_mut().get_mut::<Health>(enemy).unwrap().current = 20.0;
+        let sent = run(&mut app, STEP);
+        assert_eq!(state(&app), EnemyState::Flee);
+        let (direction, _, sprint) = movement(&sent, enemy);
+        assert!(direction.distance(Vec2::Y) < 1e-3, "{direction}");
+        assert!(sprint);
+
+        // And calms down once it has lost track of them.
+        let mut memory = app.world_mut().get_mut::<Memory>(enemy).unwrap();
+        memory.sees_player = false;
+        memory.last_known_position = None;
+        run(&mut app, STEP);
+        assert_eq!(state(&app), EnemyState::Idle);
+    }
+}
@@ -0,0 +1,91 @@
+mod brain;
+mod perception;
+
+use avian3d::{math::Vector, prelude::Collider};
+use bevy::{color::palettes::tailwind, prelude::*};
+
+use crate::plugins::{
+    character_controller::{CharacterControllerBundle, CharacterControllerSystems, Stamina},
+    collision::GameLayer,
+    combat::Health,
+};
+pub use brain::{Brain, MeleeAttack};
+use brain::{act, think};
+pub use perception::{Memory, Noise, Senses};
+use perception::{fade_memories, hear_noises, player_footsteps, see_player};
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<Noise>().add_systems(
+            FixedUpdate,
+            (
+                player_footsteps,
+                fade_memories,
+                see_player,
+                hear_noises,
+                think,
+                act,
+            )
+                .chain()
+                .before(CharacterControllerSystems),
+        );
+    }
+}
+
+/// The same size as the player, so they fit wherever the navmesh says.
+const ENEMY_RADIUS: f32 = 0.4;
+const ENEMY_LENGTH: f32 = 1.0;
+
+const ENEMY_HEALTH: f32 = 100.0;
+
+#[derive(Component)]
+pub struct Enemy;
+
+/// An enemy standing at `translation`, relative to its parent if it has one, that walks
+/// between the `patrol` points, given in world space.
+pub fn enemy(
+    translation: Vec3,
+    patrol: Vec<Vec3>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) -> impl Bundle {
+    (
+        Name::new("Enemy"),
+        Enemy,
+        Transform::from_translation(translation),
+        Visibility::default(),
+        CharacterControllerBundle::new(
+            Collider::capsule(ENEMY_RADIUS, ENEMY_LENGTH),
+            Vector::NEG_Y * 9.81 * 2.0,
+        )
+        .with_movement(40.0, 0.92, 30_f32.to_radians(), 0.35)
+        .with_ground_snap(0.4)
+        .with_sprint(1.4, Stamina::new(60.0, 20.0, 10.0, 1.5))
+        // Heavier than the player, so they shove props aside and are hard to knock back.
+        .with_push(110.0, 1.5)
+        .with_collision_layers(GameLayer::enemy()),
+        Brain::new(patrol),
+        Senses::default(),
+        Memory::default(),
+        MeleeAttack::default(),
+        Health::new(ENEMY_HEALTH),
+        children![
+            (
+                Mesh3d(meshes.add(Capsule3d::new(ENEMY_RADIUS, ENEMY_LENGTH))),
+                MeshMaterial3d(materials.add(Color::from(tailwind::RED_700))),
+            ),
+            // A visor, so it is clear which way the enemy is looking.
+            (
+                Mesh3d(meshes.add(Cuboid::new(0.5, 0.12, 0.1))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: tailwind::YELLOW_300.into(),
+                    emissive: LinearRgba::from(tailwind::YELLOW_300) * 4.0,
+                    ..default()
+                })),
+                Transform::from_xyz(0.0, 0.6, -ENEMY_RADIUS),
+            ),
+        ],
+    )
+}
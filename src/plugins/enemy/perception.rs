@@ -0,0 +1,194 @@
+use avian3d::prelude::{LinearVelocity, Position, SpatialQuery, SpatialQueryFilter};
+use bevy::prelude::*;
+
+use crate::plugins::{
+    character_controller::{Crouched, Grounded, Sprinting},
+    collision::GameLayer,
+    player::Player,
+};
+
+/// A sound enemies can hear from up to `radius` meters away.
+#[derive(Message)]
+pub struct Noise {
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+/// How an enemy notices the player.
+#[derive(Component)]
+pub struct Senses {
+    pub sight_range: f32,
+    /// The full width of the sight cone, in radians.
+    pub field_of_view: f32,
+    /// Height of the eyes above the center of the body.
+    pub eye_height: f32,
+    /// Scales how far away noises can be heard from.
+    pub hearing: f32,
+}
+
+impl Default for Senses {
+    fn default() -> Self {
+        Self {
+            sight_range: 18.0,
+            field_of_view: 110_f32.to_radians(),
+            eye_height: 0.6,
+            hearing: 1.0,
+        }
+    }
+}
+
+/// What an enemy knows about where the player is.
+#[derive(Component, Default)]
+pub struct Memory {
+    pub sees_player: bool,
+    /// Where the player was last seen or heard, until it is forgotten.
+    pub last_known_position: Option<Vec3>,
+    /// Seconds since `last_known_position` was last updated.
+    pub time_since_update: f32,
+}
+
+/// How long an enemy remembers where the player was, in seconds.
+const MEMORY_DURATION: f32 = 12.0;
+
+/// Noises heard through walls only carry this fraction of their radius.
+const MUFFLED_NOISE_FACTOR: f32 = 0.5;
+
+/// Time between footstep noises, in seconds.
+const FOOTSTEP_INTERVAL: f32 = 0.45;
+
+/// Footsteps are only heard above this horizontal speed, in meters per second.
+const FOOTSTEP_MIN_SPEED: f32 = 1.0;
+
+const WALKING_NOISE_RADIUS: f32 = 5.0;
+
+const SPRINTING_NOISE_RADIUS: f32 = 12.0;
+
+/// Makes footstep noises while the player walks or runs. Crouching is silent.
+#[allow(clippy::type_complexity)]
+pub(super) fn player_footsteps(
+    time: Res<Time>,
+    mut timer: Local<f32>,
+    mut noise_writer: MessageWriter<Noise>,
+    player: Single<
+        (
+            &Position,
+            &LinearVelocity,
+            Has<Grounded>,
+            Has<Sprinting>,
+            Has<Crouched>,
+        ),
+        With<Player>,
+    >,
+) {
+    let (position, linear_velocity, is_grounded, is_sprinting, is_crouched) = player.into_inner();
+    let speed = Vec2::new(linear_velocity.x, linear_velocity.z).length();
+    if !is_grounded || is_crouched || speed < FOOTSTEP_MIN_SPEED {
+        *timer = 0.0;
+        return;
+    }
+
+    *timer -= time.delta_secs();
+    if *timer > 0.0 {
+        return;
+    }
+    *timer = FOOTSTEP_INTERVAL;
+
+    noise_writer.write(Noise {
+        position: position.0,
+        radius: if is_sprinting {
+            SPRINTING_NOISE_RADIUS
+        } else {
+            WALKING_NOISE_RADIUS
+        },
+    });
+}
+
+/// Forgets where the player was once it has been too long.
+pub(super) fn fade_memories(time: Res<Time>, mut memories: Query<&mut Memory>) {
+    for mut memory in &mut memories {
+        memory.time_since_update += time.delta_secs();
+        if memory.time_since_update > MEMORY_DURATION {
+            memory.last_known_position = None;
+        }
+    }
+}
+
+/// Looks for the player inside each enemy's sight cone, with a raycast for line of sight.
+pub(super) fn see_player(
+    spatial_query: SpatialQuery,
+    player: Single<(Entity, &Position), With<Player>>,
+    mut enemies: Query<(Entity, &Position, &Transform, &Senses, &mut Memory)>,
+) {
+    let (player_entity, player_position) = player.into_inner();
+
+    for (entity, position, transform, senses, mut memory) in &mut enemies {
+        let eye = position.0 + Vec3::Y * senses.eye_height;
+        let to_player = player_position.0 - eye;
+        let distance = to_player.length();
+
+        let in_cone = distance <= senses.sight_range
+            && transform.forward().angle_between(to_player) <= senses.field_of_view * 0.5;
+        let sees_player = in_cone
+            && Dir3::new(to_player).is_ok_and(|direction| {
+                spatial_query
+                    .cast_ray(
+                        eye,
+                        direction,
+                        distance,
+                        true,
+                        &SpatialQueryFilter::from_mask([GameLayer::World, GameLayer::Player])
+                            .with_excluded_entities([entity]),
+                    )
+                    .is_some_and(|hit| hit.entity == player_entity)
+            });
+
+        memory.sees_player = sees_player;
+        if sees_player {
+            memory.last_known_position = Some(player_position.0);
+            memory.time_since_update = 0.0;
+        }
+    }
+}
+
+/// Points enemies that hear a noise towards where it came from.
+pub(super) fn hear_noises(
+    spatial_query: SpatialQuery,
+    mut noise_reader: MessageReader<Noise>,
+    mut enemies: Query<(&Position, &Senses, &mut Memory)>,
+) {
+    for noise in noise_reader.read() {
+        for (position, senses, mut memory) in &mut enemies {
+            // Seeing the player beats hearing them.
+            if memory.sees_player {
+                continue;
+            }
+
+            let to_noise = noise.position - position.0;
+            let distance = to_noise.length();
+            let mut radius = noise.radius * senses.hearing;
+            if distance > radius {
+                continue;
+            }
+
+            let is_muffled = Dir3::new(to_noise).is_ok_and(|direction| {
+                spatial_query
+                    .cast_ray(
+                        position.0,
+                        direction,
+                        distance,
+                        true,
+                        &SpatialQueryFilter::from_mask(GameLayer::World),
+                    )
+                    .is_some()
+            });
+            if is_muffled {
+                radius *= MUFFLED_NOISE_FACTOR;
+            }
+
+            if distance <= radius {
+                memory.last_known_position = Some(noise.position);
+                memory.time_since_update = 0.0;
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::plugins::{
+    character_controller::{StaminaExhausted, StaminaRecovered},
+    dungeon::{DoorLocked, KeyPickedUp},
+    player::Player,
+};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud).add_systems(
+            Update,
+            (stamina_notices, door_notices, fade_notices).chain(),
+        );
+    }
+}
+
+/// A line of text near the bottom of the screen that tells the player what just happened.
+#[derive(Component, Default)]
+struct Notice {
+    /// Seconds left before the text is gone.
+    timer: f32,
+}
+
+impl Notice {
+    fn show(&mut self, text: &mut Text, message: impl Into<String>) {
+        text.0 = message.into();
+        self.timer = NOTICE_DURATION;
+    }
+}
+
+/// How long a notice stays on screen, in seconds.
+const NOTICE_DURATION: f32 = 2.5;
+
+/// How long a notice takes to fade out at the end, in seconds.
+const NOTICE_FADE_TIME: f32 = 0.5;
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Notice"),
+        Notice::default(),
+        Text::default(),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::NONE),
+        TextLayout::new_with_justify(Justify::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(20.0),
+            width: Val::Percent(100.0),
+            ..default()
+        },
+    ));
+}
+
+/// Tells the player when they run out of breath, and when they can sprint again.
+fn stamina_notices(
+    mut exhausted_reader: MessageReader<StaminaExhausted>,
+    mut recovered_reader: MessageReader<StaminaRecovered>,
+    player: Single<Entity, With<Player>>,
+    notice: Single<(&mut Notice, &mut Text)>,
+) {
+    let (mut notice, mut text) = notice.into_inner();
+
+    for exhausted in exhausted_reader.read() {
+        if exhausted.entity == *player {
+            notice.show(&mut text, "Out of breath");
+        }
+    }
+    for recovered in recovered_reader.read() {
+        if recovered.entity == *player {
+            notice.show(&mut text, "Caught your breath");
+        }
+    }
+}
+
+/// Tells the player about keys they pick up and doors they can't open yet.
+fn door_notices(
+    mut picked_up_reader: MessageReader<KeyPickedUp>,
+    mut locked_reader: MessageReader<DoorLocked>,
+    notice: Single<(&mut Notice, &mut Text)>,
+) {
+    let (mut notice, mut text) = notice.into_inner();
+
+    for picked_up in picked_up_reader.read() {
+        notice.show(&mut text, format!("Picked up key {}", picked_up.key + 1));
+    }
+    for locked in locked_reader.read() {
+        notice.show(
+            &mut text,
+            format!("The door is locked. Needs key {}", locked.lock + 1),
+        );
+    }
+}
+
+fn fade_notices(time: Res<Time>, mut notices: Query<(&mut Notice, &mut TextColor)>) {
+    for (mut notice, mut color) in &mut notices {
+        notice.timer = (notice.timer - time.delta_secs()).max(0.0);
+        color.0 = Color::WHITE.with_alpha((notice.timer / NOTICE_FADE_TIME).min(1.0));
+    }
+}
@@ -1,4 +1,17 @@
+pub mod actions;
+pub mod camera;
+pub mod camera_effects;
 pub mod character_controller;
+pub mod collision;
+pub mod combat;
+pub mod cursor;
 pub mod dungeon;
+pub mod enemy;
+pub mod hud;
+pub mod navigation;
 pub mod player;
+pub mod ron_asset;
+pub mod settings;
+pub mod spectator;
 pub mod testbed;
+pub mod view_model;
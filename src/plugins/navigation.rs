@@ -0,0 +1,718 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use avian3d::prelude::{
+    Collider, ColliderAabb, ColliderDisabled, ColliderOf, PhysicsSystems, RigidBody,
+    SimpleCollider, SpatialQuery, SpatialQueryFilter,
+};
+use bevy::{
+    color::palettes::tailwind,
+    ecs::{entity::EntityHashMap, system::SystemParam},
+    prelude::*,
+};
+
+use crate::plugins::{
+    character_controller::{MaxSlopeAngle, MaxStepHeight},
+    collision::GameLayer,
+    player::Player,
+};
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavMeshSettings>()
+            .init_resource::<ChangedGeometry>()
+            .add_systems(PostStartup, match_agent_to_player)
+            .add_systems(Update, draw_navmeshes)
+            .add_systems(PostUpdate, track_changed_geometry)
+            // Bake once physics has stepped, so spatial queries see new and changed colliders.
+            .add_systems(FixedPostUpdate, bake_navmeshes.after(PhysicsSystems::Last));
+    }
+}
+
+/// The agent the navmesh is baked for, and how finely.
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct NavMeshSettings {
+    pub agent_radius: f32,
+    pub agent_height: f32,
+    /// Steepest walkable slope, in radians.
+    pub max_slope_angle: f32,
+    /// Tallest ledge the agent can step up or down.
+    pub max_step_height: f32,
+    /// Width of a navmesh cell, in meters.
+    pub cell_size: f32,
+    pub debug_draw: bool,
+    /// Draws the path from the player to this point while `debug_draw` is on.
+    pub debug_path_target: Option<Vec3>,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            agent_radius: 0.4,
+            agent_height: 1.8,
+            max_slope_angle: 30_f32.to_radians(),
+            max_step_height: 0.35,
+            cell_size: 0.5,
+            debug_draw: false,
+            debug_path_target: None,
+        }
+    }
+}
+
+/// A box to bake a [`NavMesh`] in, centered on the entity. Everything walkable inside it
+/// becomes part of the navmesh, and nothing above the top of it is considered.
+#[derive(Component)]
+#[require(NavMesh)]
+pub struct NavMeshVolume {
+    pub half_extents: Vec3,
+}
+
+impl NavMeshVolume {
+    pub fn new(half_extents: Vec3) -> Self {
+        Self { half_extents }
+    }
+}
+
+/// Where an agent can walk within a [`NavMeshVolume`], as a grid of cells with the height
+/// of the ground in each.
+#[derive(Component, Default)]
+pub struct NavMesh {
+    /// The bottom corner of the first cell.
+    origin: Vec3,
+    size: IVec2,
+    cell_size: f32,
+    volume_height: f32,
+    max_step_height: f32,
+    /// The ground height of each walkable cell, row by row.
+    heights: Vec<Option<f32>>,
+    /// Areas to bake again, as world space minimum and maximum corners.
+    dirty: Vec<(Vec3, Vec3)>,
+    is_baked: bool,
+}
+
+/// Static colliders that were added, changed, moved, or turned on or off since the last
+/// bake, and the areas left behind by those that were removed.
+#[derive(Resource, Default)]
+struct ChangedGeometry {
+    changed: Vec<Entity>,
+    removed: Vec<(Vec3, Vec3)>,
+    /// The bounds of each static collider at the last bake, as minimum and maximum
+    /// corners, so that the area it leaves is baked again once it moves or is removed.
+    baked: EntityHashMap<(Vec3, Vec3)>,
+}
+
+const NEIGHBORS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+/// How many cells around a point to look for walkable ground when it isn't on the navmesh.
+const NEAREST_CELL_SEARCH_RADIUS: i32 = 2;
+
+impl NavMesh {
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.size.x + cell.x) as usize
+    }
+
+    fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size).all()
+    }
+
+    fn cell_at(&self, point: Vec3) -> IVec2 {
+        ((point - self.origin).xz() / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    fn height(&self, cell: IVec2) -> Option<f32> {
+        if self.in_bounds(cell) {
+            self.heights[self.index(cell)]
+        } else {
+            None
+        }
+    }
+
+    /// The point on the ground in the middle of a walkable cell.
+    fn cell_point(&self, cell: IVec2) -> Option<Vec3> {
+        self.height(cell).map(|height| {
+            let center = (cell.as_vec2() + 0.5) * self.cell_size;
+            Vec3::new(self.origin.x + center.x, height, self.origin.z + center.y)
+        })
+    }
+
+    /// Whether an agent can walk directly between two neighboring cells.
+    fn is_connected(&self, from: IVec2, to: IVec2) -> bool {
+        let (Some(from_height), Some(to_height)) = (self.height(from), self.height(to)) else {
+            return false;
+        };
+        if (from_height - to_height).abs() > self.max_step_height {
+            return false;
+        }
+
+        // Don't cut corners on diagonals.
+        let step = to - from;
+        step.x == 0
+            || step.y == 0
+            || (self.is_connected(from, from + IVec2::new(step.x, 0))
+                && self.is_connected(from, from + IVec2::new(0, step.y)))
+    }
+
+    /// Whether the point is above the navmesh and below the top of its volume.
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.in_bounds(self.cell_at(point))
+            && point.y >= self.origin.y
+            && point.y <= self.origin.y + self.volume_height
+    }
+
+    /// The walkable cell closest to a point, if there is one nearby.
+    fn nearest_cell(&self, point: Vec3) -> Option<IVec2> {
+        let cell = self.cell_at(point);
+        let radius = NEAREST_CELL_SEARCH_RADIUS;
+        (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| cell + IVec2::new(x, y)))
+            .filter_map(|cell| Some((cell, self.cell_point(cell)?)))
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(point)
+                    .total_cmp(&b.distance_squared(point))
+            })
+            .map(|(cell, _)| cell)
+    }
+
+    /// Finds a walkable path between two points with A*, straightened wherever the way
+    /// is clear. The points are on the ground, from the cell at `start` to `end`.
+    pub fn find_path(&self, start: Vec3, end: Vec3) -> Option<Vec<Vec3>> {
+        let start_cell = self.nearest_cell(start)?;
+        let end_cell = self.nearest_cell(end)?;
+        let end_point = self.cell_point(end_cell)?;
+
+        let mut came_from = vec![None; self.heights.len()];
+        let mut cost = vec![f32::INFINITY; self.heights.len()];
+        let mut open = BinaryHeap::new();
+        cost[self.index(start_cell)] = 0.0;
+        open.push(OpenCell {
+            cell: start_cell,
+            estimate: 0.0,
+        });
+
+        while let Some(OpenCell { cell, .. }) = open.pop() {
+            if cell == end_cell {
+                break;
+            }
+            let point = self.cell_point(cell)?;
+
+            for &step in &NEIGHBORS {
+                let next = cell + step;
+                if !self.is_connected(cell, next) {
+                    continue;
+                }
+                let next_point = self.cell_point(next)?;
+                let next_cost = cost[self.index(cell)] + point.distance(next_point);
+                let next_index = self.index(next);
+                if next_cost < cost[next_index] {
+                    cost[next_index] = next_cost;
+                    came_from[next_index] = Some(cell);
+                    open.push(OpenCell {
+                        cell: next,
+                        estimate: next_cost + next_point.distance(end_point),
+                    });
+                }
+            }
+        }
+
+        if start_cell != end_cell && came_from[self.index(end_cell)].is_none() {
+            return None;
+        }
+
+        let mut cells = vec![end_cell];
+        while let Some(previous) = came_from[self.index(*cells.last()?)] {
+            cells.push(previous);
+        }
+        cells.reverse();
+
+        // Skip every cell that can be seen in a straight line from an earlier one.
+        let mut path = vec![self.cell_point(cells[0])?];
+        let mut from = 0;
+        while from < cells.len() - 1 {
+            let mut to = cells.len() - 1;
+            while to > from + 1 && !self.is_clear(cells[from], cells[to]) {
+                to -= 1;
+            }
+            path.push(self.cell_point(cells[to])?);
+            from = to;
+        }
+        if let Some(last) = path.last_mut()
+            && self.contains(end)
+            && self.cell_at(end) == end_cell
+        {
+            *last = end.with_y(last.y);
+        }
+
+        Some(path)
+    }
+
+    /// Whether an agent can walk in a straight line between two cells.
+    fn is_clear(&self, from: IVec2, to: IVec2) -> bool {
+        let steps = (to - from).abs().max_element() * 2;
+        let mut previous = from;
+        for step in 1..=steps {
+            let cell = (from.as_vec2() + (to - from).as_vec2() * step as f32 / steps as f32)
+                .round()
+                .as_ivec2();
+            if cell != previous && !self.is_connected(previous, cell) {
+                return false;
+            }
+            previous = cell;
+        }
+        true
+    }
+
+    fn mark_dirty(&mut self, min: Vec3, max: Vec3) {
+        self.dirty.push((min, max));
+    }
+}
+
+/// A cell waiting to be explored, ordered so the heap pops the lowest estimate first.
+struct OpenCell {
+    cell: IVec2,
+    estimate: f32,
+}
+
+impl PartialEq for OpenCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for OpenCell {}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Finds paths on whichever navmesh holds both ends.
+#[derive(SystemParam)]
+pub struct NavMeshes<'w, 's> {
+    navmeshes: Query<'w, 's, &'static NavMesh>,
+}
+
+impl NavMeshes<'_, '_> {
+    pub fn find_path(&self, start: Vec3, end: Vec3) -> Option<Vec<Vec3>> {
+        self.navmeshes
+            .iter()
+            .filter(|navmesh| navmesh.contains(start) && navmesh.contains(end))
+            .find_map(|navmesh| navmesh.find_path(start, end))
+    }
+}
+
+/// Bakes for an agent the size of the player, with the same slope and step limits.
+fn match_agent_to_player(
+    mut settings: ResMut<NavMeshSettings>,
+    player: Single<(&Collider, &MaxSlopeAngle, &MaxStepHeight), With<Player>>,
+) {
+    let (collider, max_slope_angle, max_step_height) = player.into_inner();
+    let aabb = collider.aabb(Vec3::ZERO, Quat::IDENTITY);
+    let size = aabb.max - aabb.min;
+
+    settings.agent_radius = size.x * 0.5;
+    settings.agent_height = size.y;
+    settings.max_slope_angle = **max_slope_angle;
+    settings.max_step_height = **max_step_height;
+}
+
+#[allow(clippy::type_complexity)]
+fn track_changed_geometry(
+    mut changed_geometry: ResMut<ChangedGeometry>,
+    changed: Query<
+        (Entity, &ColliderOf),
+        Or<(
+            Changed<Collider>,
+            Changed<GlobalTransform>,
+            Added<ColliderDisabled>,
+        )>,
+    >,
+    mut enabled: RemovedComponents<ColliderDisabled>,
+    mut removed: RemovedComponents<Collider>,
+    colliders: Query<&ColliderOf>,
+    bodies: Query<&RigidBody>,
+) {
+    let is_static = |collider_of: &ColliderOf| {
+        bodies
+            .get(collider_of.body)
+            .is_ok_and(|body| body.is_static())
+    };
+
+    for (entity, collider_of) in &changed {
+        if is_static(collider_of) {
+            changed_geometry.changed.push(entity);
+        }
+    }
+    for entity in enabled.read() {
+        if colliders.get(entity).is_ok_and(is_static) {
+            changed_geometry.changed.push(entity);
+        }
+    }
+    // Its bounds are gone along with it, so use the ones it was baked with.
+    for entity in removed.read() {
+        if let Some(area) = changed_geometry.baked.remove(&entity) {
+            changed_geometry.removed.push(area);
+        }
+    }
+}
+
+fn bake_navmeshes(
+    settings: Res<NavMeshSettings>,
+    spatial_query: SpatialQuery,
+    mut changed_geometry: ResMut<ChangedGeometry>,
+    aabbs: Query<&ColliderAabb>,
+    colliders: Query<&ColliderOf>,
+    bodies: Query<&RigidBody>,
+    mut volumes: Query<(&NavMeshVolume, &GlobalTransform, &mut NavMesh)>,
+) {
+    let ChangedGeometry {
+        changed,
+        removed,
+        baked,
+    } = &mut *changed_geometry;
+    let mut areas = std::mem::take(removed);
+    for entity in changed.drain(..) {
+        // Both where a collider was and where it is now may have changed.
+        areas.extend(baked.remove(&entity));
+        if let Ok(aabb) = aabbs.get(entity) {
+            baked.insert(entity, (aabb.min, aabb.max));
+            areas.push((aabb.min, aabb.max));
+        }
+    }
+
+    // Anything within an agent's radius of a changed collider may have changed.
+    let margin = Vec3::splat(settings.agent_radius + settings.cell_size);
+    for (min, max) in areas {
+        for (_, _, mut navmesh) in &mut volumes {
+            navmesh.mark_dirty(min - margin, max + margin);
+        }
+    }
+
+    let baker = Baker {
+        settings: &settings,
+        spatial_query: &spatial_query,
+        filter: SpatialQueryFilter::from_mask(GameLayer::World),
+        is_static: &|entity| {
+            colliders
+                .get(entity)
+                .and_then(|collider_of| bodies.get(collider_of.body))
+                .is_ok_and(|body| body.is_static())
+        },
+        agent: Collider::capsule(
+            settings.agent_radius,
+            (settings.agent_height - settings.agent_radius * 2.0).max(0.0),
+        ),
+    };
+
+    for (volume, global_transform, mut navmesh) in &mut volumes {
+        if !navmesh.is_baked || settings.is_changed() {
+            let min = global_transform.translation() - volume.half_extents;
+            let size = (volume.half_extents.xz() * 2.0 / settings.cell_size)
+                .ceil()
+                .as_ivec2();
+            *navmesh = NavMesh {
+                origin: min,
+                size,
+                cell_size: settings.cell_size,
+                volume_height: volume.half_extents.y * 2.0,
+                max_step_height: settings.max_step_height,
+                heights: vec![None; (size.x * size.y) as usize],
+                dirty: Vec::new(),
+                is_baked: true,
+            };
+            baker.bake(&mut navmesh, IVec2::ZERO, size - IVec2::ONE);
+            continue;
+        }
+
+        for (min, max) in std::mem::take(&mut navmesh.dirty) {
+            let min_cell = navmesh.cell_at(min).max(IVec2::ZERO);
+            let max_cell = navmesh.cell_at(max).min(navmesh.size - IVec2::ONE);
+            if min_cell.cmple(max_cell).all() {
+                baker.bake(&mut navmesh, min_cell, max_cell);
+            }
+        }
+    }
+}
+
+/// Probes the static world to find where an agent can stand.
+struct Baker<'a, 'w, 's> {
+    settings: &'a NavMeshSettings,
+    spatial_query: &'a SpatialQuery<'w, 's>,
+    filter: SpatialQueryFilter,
+    is_static: &'a dyn Fn(Entity) -> bool,
+    agent: Collider,
+}
+
+impl Baker<'_, '_, '_> {
+    /// Bakes the cells from `min` to `max`, inclusive.
+    fn bake(&self, navmesh: &mut NavMesh, min: IVec2, max: IVec2) {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = IVec2::new(x, y);
+                let index = navmesh.index(cell);
+                let center = (cell.as_vec2() + 0.5) * navmesh.cell_size;
+                let top = navmesh.origin + Vec3::new(center.x, navmesh.volume_height, center.y);
+                navmesh.heights[index] = self.ground_height(top, navmesh.volume_height);
+            }
+        }
+    }
+
+    /// The height of the walkable ground below `top`, if the agent fits on it.
+    fn ground_height(&self, top: Vec3, depth: f32) -> Option<f32> {
+        let hit = self.spatial_query.cast_ray_predicate(
+            top,
+            Dir3::NEG_Y,
+            depth,
+            true,
+            &self.filter,
+            self.is_static,
+        )?;
+        if hit.normal.y < self.settings.max_slope_angle.cos() {
+            return None;
+        }
+        let ground = top.y - hit.distance;
+
+        // Obstacles lower than a step can be walked over, so the agent stands a step up.
+        let agent_center = Vec3::new(
+            top.x,
+            ground + self.settings.max_step_height + self.settings.agent_height * 0.5,
+            top.z,
+        );
+        let mut is_blocked = false;
+        self.spatial_query.shape_intersections_callback(
+            &self.agent,
+            agent_center,
+            Quat::IDENTITY,
+            &self.filter,
+            |entity| {
+                is_blocked = (self.is_static)(entity);
+                !is_blocked
+            },
+        );
+
+        (!is_blocked).then_some(ground)
+    }
+}
+
+fn draw_navmeshes(
+    mut gizmos: Gizmos,
+    settings: Res<NavMeshSettings>,
+    navmeshes: NavMeshes,
+    player: Single<&Transform, With<Player>>,
+) {
+    if !settings.debug_draw {
+        return;
+    }
+
+    let lift = Vec3::Y * 0.05;
+    for navmesh in &navmeshes.navmeshes {
+        for y in 0..navmesh.size.y {
+            for x in 0..navmesh.size.x {
+                let cell = IVec2::new(x, y);
+                let Some(point) = navmesh.cell_point(cell) else {
+                    continue;
+                };
+                for step in [IVec2::X, IVec2::Y] {
+                    if navmesh.is_connected(cell, cell + step)
+                        && let Some(next) = navmesh.cell_point(cell + step)
+                    {
+                        gizmos.line(point + lift, next + lift, tailwind::CYAN_400);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(target) = settings.debug_path_target
+        && let Some(path) = navmeshes.find_path(player.translation, target)
+    {
+        gizmos.linestrip(
+            path.iter().map(|point| *point + lift * 2.0),
+            tailwind::YELLOW_400,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use avian3d::prelude::PhysicsPlugins;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    /// A navmesh of 1 m cells with a row of `rows` for each row of cells along Z. `.` is
+    /// walkable ground, `^` is ground a meter higher, and anything else can't be walked on.
+    fn navmesh(rows: &[&str]) -> NavMesh {
+        NavMesh {
+            origin: Vec3::ZERO,
+            size: IVec2::new(rows[0].len() as i32, rows.len() as i32),
+            cell_size: 1.0,
+            volume_height: 4.0,
+            max_step_height: 0.35,
+            heights: rows
+                .iter()
+                .flat_map(|row| row.chars())
+                .map(|tile| match tile {
+                    '.' => Some(0.0),
+                    '^' => Some(1.0),
+                    _ => None,
+                })
+                .collect(),
+            dirty: Vec::new(),
+            is_baked: true,
+        }
+    }
+
+    /// The middle of the cell in column `x` and row `z`, on the ground.
+    fn point(x: i32, z: i32) -> Vec3 {
+        Vec3::new(x as f32 + 0.5, 0.0, z as f32 + 0.5)
+    }
+
+    #[test]
+    fn paths_across_open_ground_are_straight() {
+        let navmesh = navmesh(&["....", "....", "...."]);
+        assert_eq!(
+            navmesh.find_path(point(0, 0), point(3, 2)),
+            Some(vec![point(0, 0), point(3, 2)])
+        );
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let navmesh = navmesh(&["..#..", "..#..", "....."]);
+        let path = navmesh.find_path(point(0, 0), point(4, 0)).unwrap();
+
+        assert_eq!(path.first(), Some(&point(0, 0)));
+        assert_eq!(path.last(), Some(&point(4, 0)));
+        assert!(path.len() > 2, "{path:?}");
+        for segment in path.windows(2) {
+            let (from, to) = (navmesh.cell_at(segment[0]), navmesh.cell_at(segment[1]));
+            assert!(navmesh.is_clear(from, to), "{path:?}");
+        }
+    }
+
+    #[test]
+    fn paths_do_not_cut_corners() {
+        let blocked = navmesh(&[".#", "#."]);
+        assert_eq!(blocked.find_path(point(0, 0), point(1, 1)), None);
+
+        let open = navmesh(&["..", "#."]);
+        assert_eq!(
+            open.find_path(point(0, 0), point(1, 1)),
+            Some(vec![point(0, 0), point(1, 0), point(1, 1)])
+        );
+    }
+
+    #[test]
+    fn there_is_no_path_to_unreachable_cells() {
+        let walled = navmesh(&["..#.."]);
+        assert_eq!(walled.find_path(point(0, 0), point(4, 0)), None);
+
+        // Too high to step up onto.
+        let ledge = navmesh(&["..^^"]);
+        assert_eq!(ledge.find_path(point(0, 0), point(3, 0)), None);
+    }
+
+    /// A headless app with physics and navmesh baking that runs one fixed tick per update.
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            TransformPlugin,
+            PhysicsPlugins::default(),
+        ))
+        .init_asset::<Mesh>()
+        .init_resource::<NavMeshSettings>()
+        .init_resource::<ChangedGeometry>()
+        // The systems of `NavigationPlugin` that don't need a player or gizmos.
+        .add_systems(PostUpdate, track_changed_geometry)
+        .add_systems(FixedPostUpdate, bake_navmeshes.after(PhysicsSystems::Last))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ));
+        app
+    }
+
+    fn spawn_box(app: &mut App, center: Vec3, size: Vec3) -> Entity {
+        app.world_mut()
+            .spawn((
+                RigidBody::Static,
+                Collider::cuboid(size.x, size.y, size.z),
+                Transform::from_translation(center),
+                GameLayer::world(),
+            ))
+            .id()
+    }
+
+    fn run(app: &mut App) {
+        for _ in 0..10 {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn opening_a_door_rebakes_only_the_cells_around_it() {
+        let mut app = app();
+        spawn_box(&mut app, Vec3::NEG_Y * 0.5, Vec3::new(10.0, 1.0, 5.0));
+        // A wall across the middle, with a doorway 1.5 m wide.
+        for side in [-1.0, 1.0] {
+            spawn_box(
+                &mut app,
+                Vec3::new(0.0, 2.0, side * 1.625),
+                Vec3::new(0.5, 4.0, 1.75),
+            );
+        }
+        let door = spawn_box(&mut app, Vec3::Y * 2.0, Vec3::new(0.5, 4.0, 1.5));
+        let volume = app
+            .world_mut()
+            .spawn((
+                NavMeshVolume::new(Vec3::new(5.0, 2.0, 2.5)),
+                Transform::from_xyz(0.0, 1.0, 0.0),
+            ))
+            .id();
+        run(&mut app);
+
+        let (left, right) = (Vec3::new(-3.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0));
+        let navmesh = app.world().get::<NavMesh>(volume).unwrap();
+        assert!(navmesh.is_baked);
+        assert!(navmesh.height(IVec2::ZERO).is_some());
+        assert_eq!(navmesh.find_path(left, right), None);
+
+        // A height no bake would give, to tell whether this corner is baked again.
+        let corner = IVec2::ZERO;
+        let mut navmesh = app.world_mut().get_mut::<NavMesh>(volume).unwrap();
+        let index = navmesh.index(corner);
+        navmesh.heights[index] = Some(123.0);
+
+        app.world_mut().entity_mut(door).insert(ColliderDisabled);
+        run(&mut app);
+
+        let navmesh = app.world().get::<NavMesh>(volume).unwrap();
+        assert!(navmesh.find_path(left, right).is_some());
+        assert_eq!(navmesh.height(corner), Some(123.0));
+        assert!(navmesh.dirty.is_empty());
+    }
+}
@@ -1,14 +1,24 @@
-use avian3d::{PhysicsPlugins, math::Vector, prelude::Collider};
+use avian3d::{
+    PhysicsPlugins,
+    math::{Quaternion, Vector},
+    prelude::Collider,
+};
 use bevy::camera::visibility::RenderLayers;
 use bevy::prelude::*;
 
-use crate::plugins::character_controller::{CameraSensitivity, CharacterControllerBundle};
+use crate::plugins::{
+    camera::{CameraMode, CameraRig, SpringArm},
+    camera_effects::CameraEffects,
+    character_controller::{CameraSensitivity, CharacterControllerBundle, StickSensitivity},
+    collision::GameLayer,
+};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_view_model)
+            .add_systems(Update, fit_body_to_crouch)
             .add_plugins(PhysicsPlugins::default());
     }
 }
@@ -23,23 +33,52 @@ pub struct WorldModelCamera;
 #[derive(Component)]
 pub struct ViewModelCamera;
 
+/// The player's own body, only rendered by the world camera in third person.
+#[derive(Component)]
+pub struct PlayerBody;
+
 pub static VIEW_MODEL_RENDER_LAYER: usize = 1;
+pub static PLAYER_BODY_RENDER_LAYER: usize = 2;
+
+const PLAYER_RADIUS: f32 = 0.4;
+const PLAYER_LENGTH: f32 = 1.0;
 
 // --- Systems ---
-fn spawn_view_model(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+fn spawn_view_model(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let camera_rig = CameraRig::default();
+
     commands.spawn((
         Player,
         CameraSensitivity::default(),
+        StickSensitivity::default(),
         Transform::from_xyz(0.0, 1.0, 0.0),
         Visibility::default(),
-        CharacterControllerBundle::new(Collider::capsule(0.4, 1.0), Vector::NEG_Y * 9.81 * 2.0)
-            .with_movement(30.0, 0.92, (30.0 as f32).to_radians()),
+        CharacterControllerBundle::new(
+            Collider::capsule(PLAYER_RADIUS, PLAYER_LENGTH),
+            Vector::NEG_Y * 9.81 * 2.0,
+        )
+        .with_movement(60.0, 0.92, (30.0 as f32).to_radians(), 0.35)
+        .with_ground_snap(0.4)
+        .with_jump(8.0, 0.15, 0.1)
+        .with_crouch(1.0, 0.5)
+        .with_collision_layers(GameLayer::player()),
         children![
+            (
+                PlayerBody,
+                Mesh3d(meshes.add(Capsule3d::new(PLAYER_RADIUS, PLAYER_LENGTH))),
+                MeshMaterial3d(materials.add(Color::srgb(0.8, 0.7, 0.6))),
+                RenderLayers::layer(PLAYER_BODY_RENDER_LAYER),
+            ),
             (
                 WorldModelCamera,
                 Camera3d::default(),
+                RenderLayers::default(),
                 Projection::from(PerspectiveProjection {
-                    fov: 90.0_f32.to_radians(),
+                    fov: camera_rig.world_fov.to_radians(),
                     ..default()
                 }),
             ),
@@ -51,11 +90,33 @@ fn spawn_view_model(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
                     ..default()
                 },
                 Projection::from(PerspectiveProjection {
-                    fov: 70.0_f32.to_radians(),
+                    fov: camera_rig.view_model_fov.to_radians(),
                     ..default()
                 }),
                 RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
             ),
         ],
+        camera_rig,
+        CameraMode::default(),
+        SpringArm::default(),
+        CameraEffects::default(),
     ));
 }
+
+/// Squashes the body while crouching so that it matches the collider as it shrinks.
+fn fit_body_to_crouch(
+    players: Query<(&Collider, &Children), (With<Player>, Changed<Collider>)>,
+    mut bodies: Query<&mut Transform, With<PlayerBody>>,
+) {
+    let standing_height = PLAYER_LENGTH + 2.0 * PLAYER_RADIUS;
+
+    for (collider, children) in &players {
+        let aabb = collider.aabb(Vector::ZERO, Quaternion::default());
+        let height = aabb.max.y - aabb.min.y;
+
+        let mut bodies = bodies.iter_many_mut(children);
+        while let Some(mut transform) = bodies.fetch_next() {
+            transform.scale.y = height / standing_height;
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+
+/// Loads an asset type straight from a RON file with the given extensions.
+#[derive(TypePath)]
+pub struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    _asset: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    pub const fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = BevyError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<A> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
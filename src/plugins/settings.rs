@@ -0,0 +1,70 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Where the settings file called `file_name` is kept: in a folder in the user's config
+/// directory, or in `config/` under the working directory if there isn't one.
+pub fn path(file_name: &str) -> PathBuf {
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+
+    config_dir
+        .map_or_else(
+            || PathBuf::from("config"),
+            |dir| dir.join(env!("CARGO_PKG_NAME")),
+        )
+        .join(file_name)
+}
+
+/// Reads a settings file written by [`save`].
+pub fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::from_str(&contents)?)
+}
+
+pub fn save<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(value, PrettyConfig::default())?,
+    )?;
+    Ok(())
+}
+
+/// Loads settings from `path`, falling back to the defaults if the file is missing or invalid.
+///
+/// A missing file is created from the defaults so there is something to edit.
+pub fn load_or_default<T: Default + Serialize + DeserializeOwned>(path: impl AsRef<Path>) -> T {
+    let path = path.as_ref();
+    if path.exists() {
+        load(path).unwrap_or_else(|error| {
+            warn!(
+                "Failed to load settings from {}, using defaults: {error}",
+                path.display()
+            );
+            T::default()
+        })
+    } else {
+        let value = T::default();
+        if let Err(error) = save(&value, path) {
+            warn!("Failed to save settings to {}: {error}", path.display());
+        }
+        value
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::{camera::visibility::RenderLayers, input::mouse::AccumulatedMouseScroll, prelude::*};
+
+use crate::plugins::{
+    actions::{ActionState, AxisAction, ButtonAction},
+    camera::CameraRig,
+    character_controller::CameraSensitivity,
+    cursor::CursorState,
+    player::{PLAYER_BODY_RENDER_LAYER, Player, ViewModelCamera, WorldModelCamera},
+};
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<ToggleSpectator>().add_systems(
+            Update,
+            (spectator_input, toggle_spectator, fly_spectator).chain(),
+        );
+    }
+}
+
+/// Switches between playing and flying around freely, e.g. from a key binding or a console.
+#[derive(Message)]
+pub struct ToggleSpectator;
+
+/// A free-flying debug camera. The player stays where it was left, still simulated.
+#[derive(Component)]
+pub struct Spectator {
+    /// Base flying speed, in meters per second. Scrolling changes it.
+    pub speed: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+/// How much faster the spectator flies while sprinting.
+const SPECTATOR_SPRINT_MULTIPLIER: f32 = 4.0;
+
+/// How much one scroll step changes the base speed.
+const SPECTATOR_SCROLL_FACTOR: f32 = 1.2;
+
+/// Run condition for player systems that should pause while spectating.
+pub fn is_spectating(spectators: Query<(), With<Spectator>>) -> bool {
+    !spectators.is_empty()
+}
+
+fn spectator_input(action_state: Res<ActionState>, mut writer: MessageWriter<ToggleSpectator>) {
+    if action_state.just_pressed(ButtonAction::ToggleSpectator) {
+        writer.write(ToggleSpectator);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn toggle_spectator(
+    mut commands: Commands,
+    mut reader: MessageReader<ToggleSpectator>,
+    spectators: Query<Entity, With<Spectator>>,
+    player: Single<(&CameraRig, &Children), With<Player>>,
+    mut player_cameras: Query<
+        (&mut Camera, &GlobalTransform, Has<WorldModelCamera>),
+        Or<(With<WorldModelCamera>, With<ViewModelCamera>)>,
+    >,
+) {
+    // Several toggles in one frame cancel out in pairs.
+    if reader.read().count().is_multiple_of(2) {
+        return;
+    }
+
+    let (camera_rig, children) = player.into_inner();
+
+    if !spectators.is_empty() {
+        for spectator in &spectators {
+            commands.entity(spectator).despawn();
+        }
+        // The spring arm decides again whether the view model should be shown.
+        for (mut camera, ..) in &mut player_cameras {
+            camera.is_active = true;
+        }
+        return;
+    }
+
+    let mut transform = Transform::default();
+    let mut cameras = player_cameras.iter_many_mut(children);
+    while let Some((mut camera, global_transform, is_world_model)) = cameras.fetch_next() {
+        camera.is_active = false;
+        if is_world_model {
+            transform = global_transform.compute_transform();
+        }
+    }
+    let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+
+    commands.spawn((
+        Name::new("Spectator"),
+        Spectator {
+            speed: 8.0,
+            yaw,
+            pitch,
+        },
+        CameraSensitivity::default(),
+        Camera3d::default(),
+        Projection::from(PerspectiveProjection {
+            fov: camera_rig.world_fov.to_radians(),
+            ..default()
+        }),
+        // The player's body is left behind, so make it visible.
+        RenderLayers::default().with(PLAYER_BODY_RENDER_LAYER),
+        Transform::from_translation(transform.translation).with_rotation(Quat::from_euler(
+            EulerRot::YXZ,
+            yaw,
+            pitch,
+            0.0,
+        )),
+    ));
+}
+
+fn fly_spectator(
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    cursor_state: Res<CursorState>,
+    accumulated_mouse_scroll: Res<AccumulatedMouseScroll>,
+    spectator: Single<(&mut Spectator, &CameraSensitivity, &mut Transform)>,
+) {
+    let (mut spectator, sensitivity, mut transform) = spectator.into_inner();
+
+    if cursor_state.is_grabbed() {
+        let look = action_state.axis(AxisAction::Look) * **sensitivity;
+        spectator.yaw -= look.x;
+        spectator.pitch = (spectator.pitch + look.y).clamp(
+            -std::f32::consts::FRAC_PI_2 + 0.01,
+            std::f32::consts::FRAC_PI_2 - 0.01,
+        );
+        transform.rotation = Quat::from_euler(EulerRot::YXZ, spectator.yaw, spectator.pitch, 0.0);
+    }
+
+    if accumulated_mouse_scroll.delta.y != 0.0 {
+        spectator.speed *= SPECTATOR_SCROLL_FACTOR.powf(accumulated_mouse_scroll.delta.y.signum());
+    }
+
+    let input = action_state.axis(AxisAction::Move).clamp_length_max(1.0);
+    let vertical = action_state.pressed(ButtonAction::Jump) as i8
+        - action_state.pressed(ButtonAction::Crouch) as i8;
+    let direction =
+        transform.forward() * input.y + transform.right() * input.x + Vec3::Y * vertical as f32;
+
+    let mut speed = spectator.speed;
+    if action_state.pressed(ButtonAction::Sprint) {
+        speed *= SPECTATOR_SPRINT_MULTIPLIER;
+    }
+
+    transform.translation += direction.clamp_length_max(1.0) * speed * time.delta_secs();
+}
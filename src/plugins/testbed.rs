@@ -1,21 +1,44 @@
-use avian3d::prelude::{Collider, RigidBody};
+use avian3d::prelude::{AngularVelocity, Collider, LinearVelocity, Mass, RigidBody};
 use bevy::{
     camera::visibility::RenderLayers,
-    color::palettes::css::{RED, SILVER},
+    color::palettes::css::{BROWN, GRAY, RED, SADDLE_BROWN, SILVER, STEEL_BLUE, TEAL},
     prelude::*,
 };
 
-use crate::plugins::player::VIEW_MODEL_RENDER_LAYER;
+use crate::plugins::{
+    enemy::enemy,
+    navigation::NavMeshVolume,
+    player::{PLAYER_BODY_RENDER_LAYER, VIEW_MODEL_RENDER_LAYER},
+};
 
 pub struct Testbed;
 
 impl Plugin for Testbed {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (setup_testbed, spawn_light, spawn_wall));
+        app.add_systems(
+            Startup,
+            (
+                setup_testbed,
+                spawn_light,
+                spawn_wall,
+                spawn_stairs,
+                spawn_crates,
+                spawn_platforms,
+                spawn_enemy,
+            ),
+        )
+        .add_systems(FixedUpdate, move_elevators);
     }
 }
 pub static DEFAULT_RENDER_LAYER: usize = 0;
 
+/// A kinematic platform that moves up and down between its base height and `height` above it.
+#[derive(Component)]
+struct Elevator {
+    height: f32,
+    period: f32,
+}
+
 fn setup_testbed(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -34,6 +57,12 @@ fn setup_testbed(
         Collider::half_space(outward_normal),
         Transform::from_xyz(0.0, 0.0, 0.0),
     ));
+
+    commands.spawn((
+        Name::new("Testbed NavMesh"),
+        NavMeshVolume::new(Vec3::new(25.0, 4.5, 25.0)),
+        Transform::from_xyz(0.0, 3.5, 0.0),
+    ));
 }
 
 fn spawn_light(mut commands: Commands) {
@@ -46,7 +75,11 @@ fn spawn_light(mut commands: Commands) {
             ..default()
         },
         Transform::from_xyz(8.0, 16.0, 8.0),
-        RenderLayers::from_layers(&[DEFAULT_RENDER_LAYER, VIEW_MODEL_RENDER_LAYER]),
+        RenderLayers::from_layers(&[
+            DEFAULT_RENDER_LAYER,
+            VIEW_MODEL_RENDER_LAYER,
+            PLAYER_BODY_RENDER_LAYER,
+        ]),
     ));
 }
 
@@ -65,3 +98,108 @@ fn spawn_wall(
         Collider::cuboid(1.01, 4.01, 6.01),
     ));
 }
+
+fn spawn_stairs(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    const STEP_COUNT: usize = 8;
+    const STEP_HEIGHT: f32 = 0.2;
+    const STEP_DEPTH: f32 = 0.4;
+
+    let stair_material = materials.add(Color::from(GRAY));
+    for i in 0..STEP_COUNT {
+        let height = STEP_HEIGHT * (i + 1) as f32;
+        commands.spawn((
+            Mesh3d(meshes.add(Cuboid::new(2.0, height, STEP_DEPTH))),
+            MeshMaterial3d(stair_material.clone()),
+            Transform::from_xyz(-4.0, height * 0.5, -2.0 - STEP_DEPTH * i as f32),
+            RigidBody::Static,
+            Collider::cuboid(2.0, height, STEP_DEPTH),
+        ));
+    }
+}
+
+fn spawn_enemy(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let patrol = vec![
+        Vec3::new(6.0, 1.0, 10.0),
+        Vec3::new(12.0, 1.0, 10.0),
+        Vec3::new(12.0, 1.0, 16.0),
+        Vec3::new(6.0, 1.0, 16.0),
+    ];
+    commands.spawn(enemy(patrol[0], patrol, &mut meshes, &mut materials));
+}
+
+fn spawn_crates(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Light crates that are easy to shove around, and a heavy one that barely moves.
+    let light_crate = meshes.add(Cuboid::from_length(0.6));
+    let light_material = materials.add(Color::from(BROWN));
+    for i in 0..4 {
+        commands.spawn((
+            Mesh3d(light_crate.clone()),
+            MeshMaterial3d(light_material.clone()),
+            Transform::from_xyz(-1.5 + i as f32, 0.3, 4.0),
+            RigidBody::Dynamic,
+            Collider::cuboid(0.6, 0.6, 0.6),
+            Mass(10.0),
+        ));
+    }
+
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(1.5))),
+        MeshMaterial3d(materials.add(Color::from(SADDLE_BROWN))),
+        Transform::from_xyz(2.0, 0.75, 7.0),
+        RigidBody::Dynamic,
+        Collider::cuboid(1.5, 1.5, 1.5),
+        Mass(400.0),
+    ));
+}
+
+fn spawn_platforms(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(2.5, 0.3, 2.5))),
+        MeshMaterial3d(materials.add(Color::from(STEEL_BLUE))),
+        Transform::from_xyz(-8.0, 0.15, 4.0),
+        RigidBody::Kinematic,
+        Collider::cuboid(2.5, 0.3, 2.5),
+        Elevator {
+            height: 5.0,
+            period: 8.0,
+        },
+    ));
+
+    commands.spawn((
+        Mesh3d(meshes.add(Cylinder::new(3.0, 0.3))),
+        MeshMaterial3d(materials.add(Color::from(TEAL))),
+        Transform::from_xyz(8.0, 0.15, -6.0),
+        RigidBody::Kinematic,
+        Collider::cylinder(3.0, 0.3),
+        AngularVelocity(Vec3::Y * 0.8),
+    ));
+}
+
+/// Drives elevators through velocity rather than position, so that whatever stands on them
+/// can inherit their motion.
+fn move_elevators(time: Res<Time>, mut elevators: Query<(&Elevator, &mut LinearVelocity)>) {
+    let t = time.elapsed_secs();
+
+    for (elevator, mut linear_velocity) in &mut elevators {
+        // The derivative of a height that eases between 0 and `height` over `period`.
+        let angular_frequency = std::f32::consts::TAU / elevator.period;
+        linear_velocity.y =
+            elevator.height * 0.5 * angular_frequency * (angular_frequency * t).sin();
+    }
+}
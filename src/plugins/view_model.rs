@@ -0,0 +1,203 @@
+use std::f32::consts::TAU;
+
+use avian3d::prelude::LinearVelocity;
+use bevy::{camera::visibility::RenderLayers, prelude::*};
+use serde::Deserialize;
+
+use crate::plugins::{
+    actions::{ActionState, AxisAction},
+    character_controller::Grounded,
+    cursor::CursorState,
+    player::{Player, VIEW_MODEL_RENDER_LAYER, ViewModelCamera},
+    ron_asset::RonAssetLoader,
+};
+
+pub struct ViewModelPlugin;
+
+impl Plugin for ViewModelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<HeldItemDefinition>()
+            .register_asset_loader(RonAssetLoader::<HeldItemDefinition>::new(&["item.ron"]))
+            .add_systems(PostStartup, spawn_held_item)
+            .add_systems(Update, (build_held_items, animate_held_items));
+    }
+}
+
+/// The item the player starts out holding.
+const DEFAULT_HELD_ITEM: &str = "items/pistol.item.ron";
+
+/// How a held item looks and moves in first person, loaded from `*.item.ron` files.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct HeldItemDefinition {
+    pub shape: HeldItemShape,
+    pub color: Srgba,
+    /// Resting position relative to the view model camera.
+    pub offset: Vec3,
+    /// Resting rotation relative to the view model camera, as yaw, pitch and roll in degrees.
+    pub rotation: Vec3,
+    /// How far the item lags behind mouse motion, in meters per pixel.
+    pub sway: f32,
+    pub max_sway: f32,
+    /// How far the item bobs at full walking speed, in meters.
+    pub bob_amplitude: f32,
+    /// Bob cycles per meter walked.
+    pub bob_frequency: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum HeldItemShape {
+    Cuboid(Vec3),
+    Cylinder { radius: f32, height: f32 },
+}
+
+impl HeldItemShape {
+    fn mesh(&self) -> Mesh {
+        match *self {
+            HeldItemShape::Cuboid(size) => Cuboid::from_size(size).into(),
+            HeldItemShape::Cylinder { radius, height } => Cylinder::new(radius, height).into(),
+        }
+    }
+}
+
+/// An item rendered by the [`ViewModelCamera`]. Its mesh is rebuilt whenever the
+/// definition is loaded or changes.
+#[derive(Component)]
+pub struct HeldItem {
+    pub definition: Handle<HeldItemDefinition>,
+    sway: Vec2,
+    bob_phase: f32,
+    bob_weight: f32,
+}
+
+impl HeldItem {
+    pub fn new(definition: Handle<HeldItemDefinition>) -> Self {
+        Self {
+            definition,
+            sway: Vec2::ZERO,
+            bob_phase: 0.0,
+            bob_weight: 0.0,
+        }
+    }
+}
+
+/// How quickly sway follows the mouse and settles back.
+const SWAY_SPEED: f32 = 10.0;
+
+/// Roll per meter of sideways sway, so the item tilts into turns.
+const SWAY_ROLL: f32 = 4.0;
+
+/// Horizontal speed at which the bob reaches its full amplitude.
+const BOB_REFERENCE_SPEED: f32 = 6.0;
+
+/// How quickly the bob fades in and out when starting and stopping.
+const BOB_BLEND_SPEED: f32 = 8.0;
+
+fn spawn_held_item(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    camera: Single<Entity, With<ViewModelCamera>>,
+) {
+    commands.spawn((
+        Name::new("Held Item"),
+        HeldItem::new(asset_server.load(DEFAULT_HELD_ITEM)),
+        Transform::default(),
+        Visibility::default(),
+        RenderLayers::layer(VIEW_MODEL_RENDER_LAYER),
+        ChildOf(*camera),
+    ));
+}
+
+fn build_held_items(
+    mut commands: Commands,
+    mut asset_events: MessageReader<AssetEvent<HeldItemDefinition>>,
+    definitions: Res<Assets<HeldItemDefinition>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    items: Query<(Entity, &HeldItem)>,
+) {
+    for event in asset_events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = *event
+        else {
+            continue;
+        };
+        let Some(definition) = definitions.get(id) else {
+            continue;
+        };
+
+        for (entity, item) in &items {
+            if item.definition.id() != id {
+                continue;
+            }
+
+            commands.entity(entity).insert((
+                Mesh3d(meshes.add(definition.shape.mesh())),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: definition.color.into(),
+                    perceptual_roughness: 0.6,
+                    ..default()
+                })),
+            ));
+        }
+    }
+}
+
+/// Lags held items behind camera motion and bobs them while walking.
+fn animate_held_items(
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    cursor_state: Res<CursorState>,
+    definitions: Res<Assets<HeldItemDefinition>>,
+    player: Single<(&LinearVelocity, Has<Grounded>), With<Player>>,
+    mut items: Query<(&mut HeldItem, &mut Transform)>,
+) {
+    let (linear_velocity, is_grounded) = player.into_inner();
+    let delta_time = time.delta_secs();
+
+    let look = if cursor_state.is_grabbed() {
+        action_state.axis(AxisAction::Look)
+    } else {
+        Vec2::ZERO
+    };
+    let speed = Vec2::new(linear_velocity.x, linear_velocity.z).length();
+
+    for (mut item, mut transform) in &mut items {
+        let Some(definition) = definitions.get(&item.definition) else {
+            continue;
+        };
+
+        let target_sway = (-look * definition.sway).clamp_length_max(definition.max_sway);
+        item.sway = item
+            .sway
+            .lerp(target_sway, 1.0 - (-SWAY_SPEED * delta_time).exp());
+
+        let target_bob_weight = if is_grounded {
+            (speed / BOB_REFERENCE_SPEED).min(1.0)
+        } else {
+            0.0
+        };
+        item.bob_weight = item.bob_weight.lerp(
+            target_bob_weight,
+            1.0 - (-BOB_BLEND_SPEED * delta_time).exp(),
+        );
+        if is_grounded {
+            item.bob_phase =
+                (item.bob_phase + speed * definition.bob_frequency * TAU * delta_time) % TAU;
+        }
+
+        // A figure eight: one sideways swing and two dips per cycle.
+        let bob = Vec3::new(
+            item.bob_phase.sin(),
+            0.5 * (2.0 * item.bob_phase).cos(),
+            0.0,
+        ) * definition.bob_amplitude
+            * item.bob_weight;
+
+        transform.translation = definition.offset + item.sway.extend(0.0) + bob;
+        transform.rotation = Quat::from_euler(
+            EulerRot::YXZ,
+            definition.rotation.x.to_radians(),
+            definition.rotation.y.to_radians(),
+            definition.rotation.z.to_radians() + item.sway.x * SWAY_ROLL,
+        );
+    }
+}